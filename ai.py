@@ -1,61 +1,857 @@
+import base64
 import json
-import os
+import logging
+import random
+import re
+import threading
+from datetime import datetime, timezone
+from urllib.parse import urlparse
 
 import requests
+from pydantic import BaseModel
 
+import config
+import errors
+import fixtures
+from models import Word
 
-def get_headers() -> dict:
-    return {
-        "Content-Type": "application/json",
-        "Authorization": f'Bearer {os.environ["AI_API_KEY"]}',
-    }
+AI_BASE_URL = "https://api.openai.com/v1"
+PROMPT_MAX_LENGTH = 400
+PROMPT_REPAIR_ATTEMPTS = 2
+CHAT_MODEL = "gpt-4"
+IMAGE_MODEL = "dall-e-3"
+MODERATION_MODEL = "omni-moderation-latest"
+EMBEDDING_MODEL = "text-embedding-3-small"
+VISION_MODEL = "gpt-4o-mini"
+SPEECH_MODEL = "gpt-4o-mini-tts"
+SPEECH_VOICE = "alloy"
+MODERATION_ATTEMPTS = 3
+PROVIDER_CONCURRENCY = 4
 
+BASE64_MIN_LENGTH = 256
+BASE64_PATTERN = re.compile(r"[A-Za-z0-9+/]+={0,2}")
+DATA_URL_PREFIX = re.compile(r"data:[\w/+.-]+;base64,")
 
-def generate_prompt(words: list[str]) -> str:
-    url = "https://api.openai.com/v1/chat/completions"
+logger = logging.getLogger(__name__)
 
-    # The note about race and ethnicity are due to some rather disturbing prompts I've gotten back, where not mentioning
-    # race made the prompt hyperfocus on everyone's race, to the point where it was creepy.
-    # The text is because Dall-E will sometimes try and put text in the image, which is not what we want.
-    # About 250 characters is about the ideal length for an image prompt
-    instructions = """
-    You are feeding into an image generation model. You will be given three words, each separated by a comma.
+# Bump these whenever the wording of a template changes, they're recorded on each day so quality changes can be
+# traced back to a template revision
+PROMPT_TEMPLATE_VERSION = 3
+IMAGE_TEMPLATE_VERSION = 3
+
+# The note about race and ethnicity are due to some rather disturbing prompts I've gotten back, where not mentioning
+# race made the prompt hyperfocus on everyone's race, to the point where it was creepy.
+# The text is because Dall-E will sometimes try and put text in the image, which is not what we want.
+# About 250 characters is about the ideal length for an image prompt
+PROMPT_INSTRUCTIONS = """
+    You are feeding into an image generation model. You will be given three words, each separated by a comma and
+    followed by whether it is an object, a gerund or a concept.
     Return a vivid description of a dream-like scene, based on the three elements the user has provided.
-    The three elements must feature prominently.
+    The three elements must feature prominently: objects as things in the scene, gerunds as what is happening in it
+    and concepts woven into what the scene shows, not only named.
     No mentions of race, ethnicity, or text should be present in your output.
-    Only return the description, as this will feed directly into the image generator.
-    Limit your output to about 250 characters.
+    Limit the description to about 250 characters.
+    """
+
+# Kept separate from the instructions above so experiment variants get the same output format
+OUTPUT_FORMAT_INSTRUCTIONS = """
+    Respond with only a JSON object with these keys:
+    "description": the scene description, which will feed directly into the image generator.
+    "title": a short, evocative title for the scene of at most five words, not using any of the three words.
+    "hint": a single sentence hinting at the three words without using any of them.
+    """
+
+# The OpenAI image API has no negative prompt parameter, so the things to avoid are spelled out in the prompt itself
+IMAGE_PROMPT_TEMPLATE = (
+    "{prompt}. In the style of {style}. You must not include any of the following in the image: {negative_prompt}."
+)
+NEGATIVE_PROMPT = "text, watermark, frame, caption"
+# dall-e-3's limit, dall-e-2 only takes 1000 and gpt-image-1 32000
+IMAGE_PROMPT_MAX_LENGTH = 4000
+
+SHORTEN_INSTRUCTIONS = """
+    You will be given three words and a description of a dream-like scene built around them, which is too long for the
+    image generation model it feeds into. Shorten it to under {max_characters} characters, keeping all three words
+    and the most vivid details. Respond with only the shortened description.
+    """
+
+# Sent back in the same conversation as the first prompt, see refine_prompt
+PROMPT_REFINE_INSTRUCTIONS = """
+    Critique your description: where is the imagery flat or generic, and is each of the three words clearly shown?
+    Then write an improved description that is more vivid and dream-like, with all three words unmistakable, still
+    about 250 characters. Respond with only the improved JSON object, in the same format as before.
+    """
+
+# For a description that moderation flagged or the image generator refused, rather than starting over
+PROMPT_REWRITE_INSTRUCTIONS = """
+    You will be given three words and a description of a dream-like scene built around them, which will feed into an
+    image generation model. The description was rejected by a content filter for: {reasons}.
+    Rewrite the description so it would pass, keeping the scene dream-like and all three words featured prominently.
+    No mentions of race, ethnicity, or text should be present in your output.
+    Limit the description to about 250 characters.
+    """
+
+PROMPT_REVISE_INSTRUCTIONS = """
+    You will be given three words and a description of a dream-like scene built around them, which was fed into an
+    image generation model. Someone looking at the image it made said: {critique}
+    Rewrite the description so the next image shows all three words clearly, keeping the scene dream-like.
+    No mentions of race, ethnicity, or text should be present in your output.
+    Limit the description to about 250 characters.
+    """
+
+IMAGE_CRITIQUE_INSTRUCTIONS = """
+    You will be given an image and the three words it was meant to depict, each with its type.
+    Respond with only a JSON object with these keys:
+    "description": a short description of what the image shows.
+    "shown": the words someone could recognize in the image, exactly as they were given.
+    "critique": what the image would need for the other words to be recognizable, or an empty string.
+    """
+
+GUESS_INSTRUCTIONS = """
+    You are playing a guessing game. The image was made from three hidden words: an object, a gerund and a concept.
+    Respond with only a JSON object with the key "guesses": your best guess at each hidden word, three single words.
+    """
+
+PROMPT_JUDGE_INSTRUCTIONS = """
+    You will be given three words and several numbered descriptions of a dream-like scene, each of which will feed
+    into an image generation model. Pick the description that is the most vivid and that makes all three words the
+    easiest to recognize in a single image.
+    Respond with only a JSON object with the key "best": the number of the description you picked.
     """
+
+
+class GeneratedPrompt(BaseModel):
+    description: str
+    title: str
+    hint: str
+
+
+class ImageCritique(BaseModel):
+    description: str
+    shown: list[str]
+    critique: str
+
+
+class PromptVariant(BaseModel):
+    name: str
+    instructions: str
+
+
+# With a [prompt_experiment] configured, each challenge gets variant "b" (the experiment's instructions) with
+# probability b_share, otherwise the regular instructions as variant "a"
+def choose_prompt_variant() -> PromptVariant:
+    b_instructions = config.get("PROMPT_EXPERIMENT_B_INSTRUCTIONS")
+    if b_instructions and random.random() < float(config.get("PROMPT_EXPERIMENT_B_SHARE", 0.5)):
+        return PromptVariant(name="b", instructions=b_instructions)
+    return PromptVariant(name="a", instructions=PROMPT_INSTRUCTIONS)
+
+
+def get_provider() -> str:
+    return urlparse(config.get("AI_BASE_URL", AI_BASE_URL)).hostname
+
+
+# Models used for each capability, as recorded in the day's metadata
+def get_models(difficulty: str | None = None) -> dict[str, str]:
+    models = {
+        "chat": config.get_for_difficulty("CHAT_MODEL", difficulty, CHAT_MODEL),
+        "image": config.get_for_difficulty("IMAGE_MODEL", difficulty, IMAGE_MODEL),
+        "moderation": config.get("MODERATION_MODEL", MODERATION_MODEL),
+        "embedding": config.get("EMBEDDING_MODEL", EMBEDDING_MODEL),
+    }
+    if (
+        config.get_bool_for_difficulty("IMAGE_CRITIQUE", difficulty)
+        or config.get_bool_for_difficulty("GUESSABILITY_CHECK", difficulty)
+        or int(config.get_for_difficulty("IMAGE_CANDIDATES", difficulty, 1)) > 1
+    ):
+        models["vision"] = config.get_for_difficulty("VISION_MODEL", difficulty, VISION_MODEL)
+    if config.get_bool("NARRATION"):
+        models["speech"] = config.get("SPEECH_MODEL", SPEECH_MODEL)
+    return models
+
+
+# Seconds to wait on each kind of request, overridable with CHAT_TIMEOUT, IMAGE_TIMEOUT and so on
+TIMEOUTS = {
+    "chat": 30,
+    "image": 120,
+    "moderation": 30,
+    "embedding": 30,
+    "speech": 60,
+}
+
+
+def get_timeout(capability: str) -> float:
+    return float(config.get(f"{capability.upper()}_TIMEOUT", TIMEOUTS[capability]))
+
+
+# The organization and project headers keep staging and production usage apart for billing
+def get_headers() -> dict:
+    headers = {
+        "Content-Type": "application/json",
+        "Authorization": f'Bearer {config.get_required("AI_API_KEY")}',
+    }
+    if config.get("OPENAI_ORGANIZATION"):
+        headers["OpenAI-Organization"] = config.get("OPENAI_ORGANIZATION")
+    if config.get("OPENAI_PROJECT"):
+        headers["OpenAI-Project"] = config.get("OPENAI_PROJECT")
+    return headers
+
+
+# Caps how many requests are in flight to each provider at once, which only matters when several days are being
+# generated together (see backfill in main.py)
+provider_slots: dict[str, threading.BoundedSemaphore] = {}
+provider_slots_lock = threading.Lock()
+
+
+def get_provider_slots(provider: str) -> threading.BoundedSemaphore:
+    with provider_slots_lock:
+        if provider not in provider_slots:
+            provider_slots[provider] = threading.BoundedSemaphore(
+                int(config.get("PROVIDER_CONCURRENCY", PROVIDER_CONCURRENCY))
+            )
+        return provider_slots[provider]
+
+
+def post(capability: str, path: str, data: dict, stream: bool = False) -> requests.Response:
+    url = f'{config.get("AI_BASE_URL", AI_BASE_URL)}/{path}'
+    fixtures_mode = fixtures.get_mode()
+    if fixtures_mode == "replay":
+        return fixtures.replay(path, data)
+
+    try:
+        with get_provider_slots(get_provider()):
+            response = requests.post(
+                url,
+                data=json.dumps(data),
+                headers=get_headers(),
+                timeout=get_timeout(capability),
+                # Recording needs the whole body anyway
+                stream=stream and fixtures_mode != "record",
+            )
+    except (requests.ConnectionError, requests.Timeout) as e:
+        raise errors.RetryableError(
+            f"Request to {url} failed: {e}", provider=get_provider(), endpoint=path
+        ) from e
+    log_exchange(url, data, response, stream and fixtures_mode != "record")
+    if fixtures_mode == "record":
+        return fixtures.record(path, data, response)
+    return response
+
+
+# Only used to check the API key and models, so it skips the fixtures and the debug log
+def get(path: str) -> requests.Response:
+    url = f'{config.get("AI_BASE_URL", AI_BASE_URL)}/{path}'
+    try:
+        return requests.get(url, headers=get_headers(), timeout=get_timeout("chat"))
+    except (requests.ConnectionError, requests.Timeout) as e:
+        raise errors.RetryableError(
+            f"Request to {url} failed: {e}", provider=get_provider(), endpoint=path
+        ) from e
+
+
+def list_models() -> list[str]:
+    response = get("models")
+    if not response.ok:
+        raise errors.from_response(response, "Failed to list models", get_provider(), "models")
+    return sorted(model["id"] for model in response.json()["data"])
+
+
+def check_model(model: str):
+    response = get(f"models/{model}")
+    if not response.ok:
+        raise errors.from_response(response, f"Model {model} isn't available", get_provider(), "models")
+
+
+# Tokens used per model by the current thread's run, read into its report at the end. Per thread for the same reason
+# as the debug log below.
+token_usage = threading.local()
+
+
+def reset_token_usage():
+    token_usage.totals = {}
+
+
+def get_token_usage() -> dict[str, dict[str, int]]:
+    return dict(getattr(token_usage, "totals", {}))
+
+
+# Chat completions and embeddings count prompt/completion tokens, the responses API input/output tokens
+def record_token_usage(model: str, usage: dict | None):
+    if not usage:
+        return
+    input_tokens = usage.get("prompt_tokens", usage.get("input_tokens", 0))
+    output_tokens = usage.get("completion_tokens", usage.get("output_tokens", 0))
+    logger.debug("%s used %s input and %s output tokens", model, input_tokens, output_tokens)
+    if not hasattr(token_usage, "totals"):
+        reset_token_usage()
+    totals = token_usage.totals.setdefault(model, {"requests": 0, "input_tokens": 0, "output_tokens": 0})
+    totals["requests"] += 1
+    totals["input_tokens"] += input_tokens
+    totals["output_tokens"] += output_tokens
+
+
+# Opt-in log of every provider request and response, for when all we'd otherwise have is a parse error. Nothing
+# secret goes in: headers are left out, the API key is scrubbed and base64 payloads (images) are truncated.
+# It's per thread so each day in a backfill logs to its own run's output directory.
+debug_log = threading.local()
+
+
+def enable_debug_log(path: str):
+    debug_log.path = path
+
+
+def redact(value):
+    if isinstance(value, dict):
+        return {key: redact(item) for key, item in value.items()}
+    if isinstance(value, list):
+        return [redact(item) for item in value]
+    if isinstance(value, str):
+        api_key = config.get("AI_API_KEY")
+        if api_key:
+            value = value.replace(api_key, "[REDACTED]")
+        payload = DATA_URL_PREFIX.sub("", value, count=1)
+        if len(payload) > BASE64_MIN_LENGTH and BASE64_PATTERN.fullmatch(payload):
+            return f"[{len(value)} characters of base64]"
+    return value
+
+
+def log_exchange(url: str, data: dict, response: requests.Response, stream: bool):
+    debug_log_path = getattr(debug_log, "path", None)
+    if debug_log_path is None:
+        return
+    if stream:
+        response_body = "[streamed]"
+    elif response.ok and response.headers.get("Content-Type", "").startswith("audio/"):
+        response_body = f"[{len(response.content)} bytes of audio]"
+    else:
+        try:
+            response_body = response.json()
+        except ValueError:
+            response_body = response.text
+    entry = {
+        "time": datetime.now(timezone.utc).isoformat(),
+        "url": url,
+        "request": redact(data),
+        "status": response.status_code,
+        "response": redact(response_body),
+    }
+    with open(debug_log_path, "a") as file:
+        file.write(json.dumps(entry) + "\n")
+
+
+# CHAT_TEMPERATURE and CHAT_TOP_P are only sent when set, as reasoning models reject them
+def get_chat_options(difficulty: str | None) -> dict:
+    options = {}
+    for key, name in [("CHAT_TEMPERATURE", "temperature"), ("CHAT_TOP_P", "top_p")]:
+        value = config.get_for_difficulty(key, difficulty)
+        if value is not None:
+            options[name] = float(value)
+    return options
+
+
+# CHAT_API picks between the chat completions API and the newer responses API, per difficulty if needed.
+# max_characters lets a streamed plain text response stop early once it's long enough, JSON is never cut short.
+def chat(
+    messages: list[dict],
+    difficulty: str | None = None,
+    json_response: bool = False,
+    max_characters: int | None = None,
+) -> str:
+    if config.get_for_difficulty("CHAT_API", difficulty, "chat_completions") == "responses":
+        return chat_with_responses(messages, difficulty, json_response)
+
     data = {
-        "model": "gpt-4",
-        "messages": [
-            {
-                "role": "system",
-                "content": instructions
-            },
-            {"role": "user", "content": ", ".join(words)},
-        ],
+        "model": config.get_for_difficulty("CHAT_MODEL", difficulty, CHAT_MODEL),
+        "messages": messages,
+        **get_chat_options(difficulty),
     }
-    response = requests.post(url, data=json.dumps(data), headers=get_headers())
+    # Only newer models support JSON mode, older ones are just asked nicely by the instructions
+    if json_response and config.get_bool("CHAT_JSON_MODE"):
+        data["response_format"] = {"type": "json_object"}
+    if config.get_bool("CHAT_STREAM"):
+        return stream_chat(data, None if json_response else max_characters)
+
+    response = post("chat", "chat/completions", data)
     if response.ok:
-        return response.json()["choices"][0]["message"]["content"]
+        body = response.json()
+        record_token_usage(data["model"], body.get("usage"))
+        if body["choices"][0].get("finish_reason") == "length":
+            logger.warning("Chat response from %s was cut off at its token limit", data["model"])
+        return body["choices"][0]["message"]["content"]
     else:
-        raise RuntimeError(
-            f"Failed to generate prompt: {response.status_code} {response.text}"
+        raise errors.from_response(
+            response, "Chat request failed", get_provider(), "chat/completions"
+        )
+
+
+# Reads the server sent events of a streamed chat completion, hanging up once max_characters have arrived
+def stream_chat(data: dict, max_characters: int | None) -> str:
+    content = ""
+    # The usage comes in a last chunk of its own, which a stream that's stopped early never gets to
+    data = {**data, "stream": True, "stream_options": {"include_usage": True}}
+    with post("chat", "chat/completions", data, stream=True) as response:
+        if not response.ok:
+            raise errors.from_response(
+                response, "Chat request failed", get_provider(), "chat/completions"
+            )
+        for line in response.iter_lines(decode_unicode=True):
+            if not line or not line.startswith("data: "):
+                continue
+            payload = line.removeprefix("data: ")
+            if payload == "[DONE]":
+                break
+            chunk = json.loads(payload)
+            record_token_usage(data["model"], chunk.get("usage"))
+            choices = chunk["choices"]
+            if choices:
+                content += choices[0]["delta"].get("content") or ""
+            if max_characters is not None and len(content) >= max_characters:
+                logger.info("Stopping chat stream early at %s characters", len(content))
+                return trim_to_sentence(content)
+    return content
+
+
+# Cutting a stream off leaves half a sentence dangling, so drop back to the last full one if there is one
+def trim_to_sentence(text: str) -> str:
+    end = max(text.rfind(". "), text.rfind("! "), text.rfind("? "))
+    return text[: end + 1] if end > 0 else text
+
+
+def chat_with_responses(
+    messages: list[dict], difficulty: str | None = None, json_response: bool = False
+) -> str:
+    data = {
+        "model": config.get_for_difficulty("CHAT_MODEL", difficulty, CHAT_MODEL),
+        "input": messages,
+        **get_chat_options(difficulty),
+    }
+    if json_response and config.get_bool("CHAT_JSON_MODE"):
+        data["text"] = {"format": {"type": "json_object"}}
+    response = post("chat", "responses", data)
+    if not response.ok:
+        raise errors.from_response(response, "Chat request failed", get_provider(), "responses")
+    body = response.json()
+    record_token_usage(data["model"], body.get("usage"))
+    if body.get("status") == "incomplete":
+        logger.warning("Chat response from %s was cut off: %s", data["model"], body.get("incomplete_details"))
+    return "".join(
+        content["text"]
+        for item in body["output"]
+        if item["type"] == "message"
+        for content in item["content"]
+        if content["type"] == "output_text"
+    )
+
+
+# Plurals are matched loosely as the model likes to write "an apple" when given "apples"
+def word_in_text(word: str, text: str) -> bool:
+    text = text.lower()
+    word = word.lower()
+    return word in text or (word.endswith("s") and word[:-1] in text)
+
+
+def get_names(words: list[Word]) -> list[str]:
+    return [word.word for word in words]
+
+
+# e.g. "lantern (object), swimming (gerund), nostalgia (concept)"
+def format_words(words: list[Word]) -> str:
+    return ", ".join(f"{word.word} ({word.type})" for word in words)
+
+
+# Returns what's wrong with a prompt as feedback for the model, or None if it's fine
+def validate_prompt(prompt: str, words: list[str]) -> str | None:
+    problems = []
+    missing_words = [word for word in words if not word_in_text(word, prompt)]
+    if missing_words:
+        problems.append(f"It must include these words: {', '.join(missing_words)}.")
+    max_length = int(config.get("PROMPT_MAX_LENGTH", PROMPT_MAX_LENGTH))
+    if len(prompt) > max_length:
+        problems.append(f"It is {len(prompt)} characters long, it must be under {max_length}.")
+    return " ".join(problems) or None
+
+
+def parse_generated_prompt(content: str) -> GeneratedPrompt:
+    content = content.strip().removeprefix("```json").removeprefix("```").removesuffix("```")
+    return GeneratedPrompt.model_validate(json.loads(content))
+
+
+def check_generated_prompt(
+    content: str, words: list[str]
+) -> tuple[GeneratedPrompt | None, str | None]:
+    try:
+        generated = parse_generated_prompt(content)
+    except ValueError:
+        return None, "Respond with only a JSON object with description, title and hint keys."
+    return generated, validate_prompt(generated.description, words)
+
+
+def generate_prompt(
+    words: list[Word],
+    difficulty: str | None = None,
+    instructions: str = PROMPT_INSTRUCTIONS,
+) -> GeneratedPrompt:
+    messages = [
+        {
+            "role": "system",
+            "content": instructions + OUTPUT_FORMAT_INSTRUCTIONS
+        },
+        {"role": "user", "content": format_words(words)},
+    ]
+    content = chat(messages, difficulty, json_response=True)
+
+    # Every so often a word goes missing, so ask again with the problem spelled out before settling
+    repair_attempts = int(config.get("PROMPT_REPAIR_ATTEMPTS", PROMPT_REPAIR_ATTEMPTS))
+    for attempt in range(repair_attempts + 1):
+        generated, problems = check_generated_prompt(content, get_names(words))
+        if problems is None or attempt == repair_attempts:
+            break
+        logger.info("Prompt needs repairing: %s", problems)
+        messages += [
+            {"role": "assistant", "content": content},
+            {"role": "user", "content": f"Please rewrite the description. {problems}"},
+        ]
+        content = chat(messages, difficulty, json_response=True)
+
+    if generated is None:
+        raise errors.QualityError(
+            f"Failed to get a usable prompt: {content}", provider=get_provider()
+        )
+    if config.get_bool_for_difficulty("PROMPT_REFINE", difficulty):
+        refined = refine_prompt(messages, content, words, difficulty)
+        if refined is not None:
+            generated, problems = refined, None
+    if problems is not None:
+        logger.warning("Using prompt that still has problems: %s", problems)
+    return generated
+
+
+# One round of critique and revision in the same conversation, for single-shot prompts that come out flat. The
+# revision is only used if it passes validation.
+def refine_prompt(
+    messages: list[dict], content: str, words: list[Word], difficulty: str | None = None
+) -> GeneratedPrompt | None:
+    messages = messages + [
+        {"role": "assistant", "content": content},
+        {"role": "user", "content": PROMPT_REFINE_INSTRUCTIONS},
+    ]
+    refined_content = chat(messages, difficulty, json_response=True)
+    refined, problems = check_generated_prompt(refined_content, get_names(words))
+    if refined is None or problems is not None:
+        logger.warning("Keeping the unrefined prompt, the refined one has problems: %s", problems or refined_content)
+        return None
+    logger.info("Refined prompt: %s", refined.description)
+    return refined
+
+
+DAY_TITLE_MAX_CHARACTERS = 80
+DAY_TITLE_INSTRUCTIONS = """
+    You will be given the description of a dream-like scene.
+    Return a short, poetic title for it of at most six words, to be used as the title of the day on a website.
+    Only return the title, without quotes.
+    """
+
+
+def generate_day_title(prompt: str) -> str:
+    title = chat(
+        [
+            {"role": "system", "content": DAY_TITLE_INSTRUCTIONS},
+            {"role": "user", "content": prompt},
+        ],
+        "dreaming",
+        max_characters=DAY_TITLE_MAX_CHARACTERS,
+    )
+    return title.strip().strip('"')
+
+
+TRANSLATE_INSTRUCTIONS = """
+    You will be given the description of a dream-like scene, written in English.
+    Translate it into the language of the locale {locale}, keeping its tone and imagery.
+    Only return the translation, without quotes.
+    """
+
+
+def translate_prompt(prompt: str, locale: str, difficulty: str | None = None) -> str:
+    translation = chat(
+        [
+            {"role": "system", "content": TRANSLATE_INSTRUCTIONS.format(locale=locale)},
+            {"role": "user", "content": prompt},
+        ],
+        difficulty,
+    )
+    return translation.strip().strip('"')
+
+
+# The text-embedding-3 models can shorten their vectors server side, which is a lot better than truncating them here
+def embed(texts: list[str], dimensions: int | None = None) -> list[list[float]]:
+    data = {
+        "model": config.get("EMBEDDING_MODEL", EMBEDDING_MODEL),
+        "input": texts,
+    }
+    if dimensions is not None:
+        data["dimensions"] = dimensions
+    response = post("embedding", "embeddings", data)
+    if not response.ok:
+        raise errors.from_response(response, "Failed to embed", get_provider(), "embeddings")
+    body = response.json()
+    record_token_usage(data["model"], body.get("usage"))
+    return [item["embedding"] for item in sorted(body["data"], key=lambda item: item["index"])]
+
+
+# Returns the moderation categories the text was flagged for, empty if it's fine
+def moderate(text: str) -> list[str]:
+    data = {
+        "model": config.get("MODERATION_MODEL", MODERATION_MODEL),
+        "input": text,
+    }
+    response = post("moderation", "moderations", data)
+    if not response.ok:
+        raise errors.from_response(
+            response, "Failed to moderate prompt", get_provider(), "moderations"
+        )
+    result = response.json()["results"][0]
+    if not result["flagged"]:
+        return []
+    return [category for category, flagged in result["categories"].items() if flagged]
+
+
+# With PROMPT_CANDIDATES above 1, that many prompts are generated and a judge call picks the one to spend the image
+# generation on. Candidates that already fail validation are only judged if none pass.
+def generate_best_prompt(
+    words: list[Word],
+    difficulty: str | None = None,
+    instructions: str = PROMPT_INSTRUCTIONS,
+) -> GeneratedPrompt:
+    count = int(config.get_for_difficulty("PROMPT_CANDIDATES", difficulty, 1))
+    candidates = [generate_moderated_prompt(words, difficulty, instructions) for _ in range(max(count, 1))]
+    valid = [
+        candidate for candidate in candidates if validate_prompt(candidate.description, get_names(words)) is None
+    ]
+    candidates = valid or candidates
+    if len(candidates) == 1:
+        return candidates[0]
+    return candidates[judge_prompts(candidates, words, difficulty)]
+
+
+# The index of the best candidate, or the first if the judge's answer can't be used
+def judge_prompts(candidates: list[GeneratedPrompt], words: list[Word], difficulty: str | None = None) -> int:
+    descriptions = "\n".join(
+        f"{number}. {candidate.description}" for number, candidate in enumerate(candidates, start=1)
+    )
+    messages = [
+        {"role": "system", "content": PROMPT_JUDGE_INSTRUCTIONS},
+        {"role": "user", "content": f"Words: {format_words(words)}\n\n{descriptions}"},
+    ]
+    content = chat(messages, difficulty, json_response=True)
+    try:
+        content = content.strip().removeprefix("```json").removeprefix("```").removesuffix("```")
+        best = int(json.loads(content)["best"])
+    except (ValueError, KeyError, TypeError):
+        logger.warning("Couldn't read the prompt judge's answer, using the first candidate: %s", content)
+        return 0
+    if not 1 <= best <= len(candidates):
+        logger.warning("Prompt judge picked %s out of %s candidates, using the first", best, len(candidates))
+        return 0
+    logger.info("Prompt judge picked candidate %s of %s", best, len(candidates))
+    return best - 1
+
+
+def rewrite_prompt(
+    generated: GeneratedPrompt, words: list[Word], reasons: str, difficulty: str | None = None
+) -> GeneratedPrompt:
+    return request_rewrite(generated, words, PROMPT_REWRITE_INSTRUCTIONS.format(reasons=reasons), difficulty)
+
+
+# For a description whose image didn't show all the words, see critique_image
+def revise_prompt(
+    generated: GeneratedPrompt, words: list[Word], critique: str, difficulty: str | None = None
+) -> GeneratedPrompt:
+    return request_rewrite(generated, words, PROMPT_REVISE_INSTRUCTIONS.format(critique=critique), difficulty)
+
+
+def request_rewrite(
+    generated: GeneratedPrompt, words: list[Word], instructions: str, difficulty: str | None = None
+) -> GeneratedPrompt:
+    messages = [
+        {"role": "system", "content": instructions + OUTPUT_FORMAT_INSTRUCTIONS},
+        {"role": "user", "content": f"Words: {format_words(words)}\n\n{generated.model_dump_json()}"},
+    ]
+    content = chat(messages, difficulty, json_response=True)
+    rewritten, problems = check_generated_prompt(content, get_names(words))
+    if rewritten is None:
+        raise errors.QualityError(f"Failed to get a usable rewritten prompt: {content}", provider=get_provider())
+    if problems is not None:
+        logger.warning("Using rewritten prompt that still has problems: %s", problems)
+    return rewritten
+
+
+# Catching a problematic description here is a lot cheaper than spending an image generation on it. A flagged one is
+# rewritten with what was flagged, which keeps the words in it better than starting over.
+def generate_moderated_prompt(
+    words: list[Word],
+    difficulty: str | None = None,
+    instructions: str = PROMPT_INSTRUCTIONS,
+) -> GeneratedPrompt:
+    generated = generate_prompt(words, difficulty, instructions)
+    if not config.get_bool("MODERATION_ENABLED", True):
+        return generated
+
+    attempts = int(config.get("MODERATION_ATTEMPTS", MODERATION_ATTEMPTS))
+    for attempt in range(attempts):
+        flagged_categories = moderate(
+            f"{generated.title}\n{generated.description}\n{generated.hint}"
+        )
+        if not flagged_categories:
+            return generated
+        logger.warning(
+            "Prompt was flagged for %s, rewriting: %s",
+            ", ".join(flagged_categories),
+            generated.description,
         )
+        if attempt < attempts - 1:
+            generated = rewrite_prompt(generated, words, ", ".join(flagged_categories), difficulty)
+    raise errors.QualityError(
+        f"Every prompt was flagged by moderation after {attempts} attempts",
+        provider=get_provider(),
+        endpoint="moderations",
+    )
+
+
+def is_content_policy_rejection(response: requests.Response) -> bool:
+    if response.status_code != 400:
+        return False
+    try:
+        return response.json()["error"]["code"] == "content_policy_violation"
+    except (ValueError, KeyError, TypeError):
+        return False
+
+
+def format_image_prompt(prompt: str, style: str, difficulty: str | None = None) -> str:
+    return IMAGE_PROMPT_TEMPLATE.format(
+        prompt=prompt,
+        style=style,
+        negative_prompt=config.get_for_difficulty("IMAGE_NEGATIVE_PROMPT", difficulty, NEGATIVE_PROMPT),
+    )
+
+
+# The style and negative prompt around the description can take the whole thing over the image model's limit, so a
+# description that doesn't fit is summarized, or failing that cut back to the last sentence that keeps every word
+def fit_image_prompt(description: str, style: str, words: list[Word], difficulty: str | None = None) -> str:
+    max_length = int(config.get_for_difficulty("IMAGE_PROMPT_MAX_LENGTH", difficulty, IMAGE_PROMPT_MAX_LENGTH))
+    budget = max_length - len(format_image_prompt("", style, difficulty))
+    if len(description) <= budget:
+        return description
+    if budget <= 0:
+        raise errors.ConfigError(
+            f"IMAGE_PROMPT_MAX_LENGTH {max_length} leaves no room for the description, the style and negative prompt "
+            "take all of it"
+        )
+
+    logger.warning("Image prompt is %s characters over the limit, shortening it", len(description) - budget)
+    shortened = chat(
+        [
+            {"role": "system", "content": SHORTEN_INSTRUCTIONS.format(max_characters=budget)},
+            {"role": "user", "content": f"Words: {format_words(words)}\n\n{description}"},
+        ],
+        difficulty,
+    ).strip()
+    if len(shortened) <= budget and validate_prompt(shortened, get_names(words)) is None:
+        return shortened
 
+    truncated = trim_to_sentence(description[:budget])
+    if all(word_in_text(word, truncated) for word in get_names(words)):
+        logger.warning("Shortened image prompt was unusable, truncating the description instead")
+        return truncated
+    raise errors.QualityError(
+        f"Couldn't fit the image prompt into {max_length} characters without losing a word", provider=get_provider()
+    )
 
-def generate_image(prompt: str) -> str:
-    url = "https://api.openai.com/v1/images/generations"
+
+def generate_image(prompt: str, style: str, difficulty: str | None = None) -> str:
     data = {
-        "prompt": f"{prompt}. You must not include any text in the image.",
-        "model": "dall-e-3",
+        "prompt": format_image_prompt(prompt, style, difficulty),
+        "model": config.get_for_difficulty("IMAGE_MODEL", difficulty, IMAGE_MODEL),
         "size": "1024x1024",
     }
-    response = requests.post(url, data=json.dumps(data), headers=get_headers())
+    response = post("image", "images/generations", data)
     if response.ok:
         return response.json()["data"][0]["url"]
+    elif is_content_policy_rejection(response):
+        raise errors.RejectedPromptError(
+            f"Image prompt was rejected by the provider's safety system: {response.text}",
+            provider=get_provider(),
+            endpoint="images/generations",
+            status=response.status_code,
+        )
     else:
-        raise RuntimeError(
-            f"Failed to generate image: {response.status_code} {response.text}"
+        raise errors.from_response(
+            response, "Failed to generate image", get_provider(), "images/generations"
         )
+
+
+# An MP3 reading of the text, for the narrated reveal
+def generate_speech(text: str) -> bytes:
+    data = {
+        "model": config.get("SPEECH_MODEL", SPEECH_MODEL),
+        "voice": config.get("SPEECH_VOICE", SPEECH_VOICE),
+        "input": text,
+        "response_format": "mp3",
+    }
+    response = post("speech", "audio/speech", data)
+    if response.ok:
+        return response.content
+    else:
+        raise errors.from_response(response, "Failed to generate speech", get_provider(), "audio/speech")
+
+
+# Shows a generated image to VISION_MODEL and returns its JSON answer. The image is sent inline since the provider's
+# URL may not be reachable (e.g. a replayed fixture).
+def ask_about_image(instructions: str, text: str, image_path: str, difficulty: str | None = None) -> str:
+    with open(image_path, "rb") as file:
+        image_data = base64.b64encode(file.read()).decode("ascii")
+    data = {
+        "model": config.get_for_difficulty("VISION_MODEL", difficulty, VISION_MODEL),
+        "messages": [
+            {"role": "system", "content": instructions},
+            {
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": text},
+                    {"type": "image_url", "image_url": {"url": f"data:image/png;base64,{image_data}"}},
+                ],
+            },
+        ],
+        "response_format": {"type": "json_object"},
+    }
+    response = post("chat", "chat/completions", data)
+    if not response.ok:
+        raise errors.from_response(response, "Image question failed", get_provider(), "chat/completions")
+    body = response.json()
+    record_token_usage(data["model"], body.get("usage"))
+    content = body["choices"][0]["message"]["content"]
+    return content.strip().removeprefix("```json").removeprefix("```").removesuffix("```")
+
+
+# Which of the words the image shows. Returns None if the answer can't be read, as this is a second opinion rather
+# than something worth failing over.
+def critique_image(image_path: str, words: list[Word], difficulty: str | None = None) -> ImageCritique | None:
+    content = ask_about_image(IMAGE_CRITIQUE_INSTRUCTIONS, format_words(words), image_path, difficulty)
+    try:
+        return ImageCritique.model_validate(json.loads(content))
+    except ValueError:
+        logger.warning("Couldn't read the image critique: %s", content)
+        return None
+
+
+# What a player might guess from the image alone, or None if the answer can't be read
+def guess_words(image_path: str, difficulty: str | None = None) -> list[str] | None:
+    content = ask_about_image(GUESS_INSTRUCTIONS, "What are the three hidden words?", image_path, difficulty)
+    try:
+        return [str(guess) for guess in json.loads(content)["guesses"]]
+    except (ValueError, KeyError, TypeError):
+        logger.warning("Couldn't read the guesses: %s", content)
+        return None
+
+
+def get_missing_words(critique: ImageCritique, words: list[Word]) -> list[str]:
+    shown = {word.lower() for word in critique.shown}
+    return [word.word for word in words if word.word.lower() not in shown]
@@ -0,0 +1,46 @@
+import logging
+
+import cdn
+import indexes
+import keys
+from migrations import fetch_day
+from models import ArchiveEntry, Day, Days, MonthArchive
+
+logger = logging.getLogger(__name__)
+
+
+def get_archive_key(month: str) -> str:
+    return keys.get_key("archive", month=month)
+
+
+def to_archive_entry(day: Day) -> ArchiveEntry:
+    return ArchiveEntry(
+        date=day.date,
+        id=day.id,
+        title=day.title,
+        thumbnail_url=(
+            day.challenges.dreaming or next(iter(day.challenges.present().values()))
+        ).image_url_jpg,
+    )
+
+
+# One file per month so the archive page doesn't need to fetch every day. The day that was just generated is passed in
+# as it may not have made it through the CDN cache yet. Days that aren't live yet are left out.
+def build_month_archive(days: Days, current_day: Day) -> MonthArchive:
+    month = current_day.date[:7]
+    entries = []
+    for entry in sorted(days.days, key=lambda entry: entry.date):
+        if not entry.date.startswith(month) or not indexes.is_live(entry.publish_at):
+            continue
+        if entry.date == current_day.date:
+            entries.append(to_archive_entry(current_day))
+        else:
+            entries.append(to_archive_entry(fetch_day(entry.date)))
+    return MonthArchive(month=month, days=entries)
+
+
+def publish_month_archive(days: Days, current_day: Day) -> tuple[str, str]:
+    archive = build_month_archive(days, current_day)
+    key = get_archive_key(archive.month)
+    logger.info("Uploading %s with %s days", key, len(archive.days))
+    return key, cdn.upload_json(archive.model_dump_json(), key)
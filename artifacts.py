@@ -0,0 +1,81 @@
+import logging
+import os
+from tempfile import mkdtemp
+
+from pydantic import BaseModel, ValidationError
+
+from ai import GeneratedPrompt
+from image import get_sha256
+from models import Word, WordHints, WordsForDay
+
+ARTIFACT_FILENAME = "challenge.json"
+IMAGE_FILENAME = "image.png"
+WORDS_FILENAME = "words.json"
+
+logger = logging.getLogger(__name__)
+
+
+# Everything a challenge needed the providers for, kept in the run's output directory so a later attempt of the same
+# run can pick up from processing instead of paying for the prompt and image again
+class ChallengeArtifact(BaseModel):
+    words: list[Word]
+    prompt_variant: str
+    prompt: GeneratedPrompt
+    hints: list[WordHints] = []
+    decoys: list[Word] = []
+    difficulty_score: float | None = None
+    guessability: float | None = None
+    image_sha256: str
+
+
+def get_artifact_dir(output_dir: str | None, difficulty: str) -> str:
+    if output_dir is None:
+        return mkdtemp(prefix=f"{difficulty}-")
+    directory = os.path.join(output_dir, "challenges", difficulty)
+    os.makedirs(directory, exist_ok=True)
+    return directory
+
+
+def get_image_path(artifact_dir: str) -> str:
+    return os.path.join(artifact_dir, IMAGE_FILENAME)
+
+
+def save(artifact_dir: str, artifact: ChallengeArtifact):
+    with open(os.path.join(artifact_dir, ARTIFACT_FILENAME), "w") as file:
+        file.write(artifact.model_dump_json(indent=2))
+
+
+# Only an artifact for the same words whose image is still intact is reused
+def load(artifact_dir: str, words: list[Word]) -> ChallengeArtifact | None:
+    path = os.path.join(artifact_dir, ARTIFACT_FILENAME)
+    image_path = get_image_path(artifact_dir)
+    if not os.path.exists(path) or not os.path.exists(image_path):
+        return None
+    try:
+        with open(path) as file:
+            artifact = ChallengeArtifact.model_validate_json(file.read())
+    except (OSError, ValidationError):
+        logger.warning("Ignoring unreadable challenge artifact %s", path)
+        return None
+    if artifact.words != words or get_sha256(image_path) != artifact.image_sha256:
+        logger.warning("Ignoring challenge artifact %s that doesn't match", path)
+        return None
+    return artifact
+
+
+# The words are picked at random, so they're kept too for a later attempt's artifacts to match
+def load_words(output_dir: str | None) -> WordsForDay | None:
+    if output_dir is None:
+        return None
+    path = os.path.join(output_dir, WORDS_FILENAME)
+    if not os.path.exists(path):
+        return None
+    with open(path) as file:
+        return WordsForDay.model_validate_json(file.read())
+
+
+def save_words(output_dir: str | None, words_for_day: WordsForDay):
+    if output_dir is None:
+        return
+    with open(os.path.join(output_dir, WORDS_FILENAME), "w") as file:
+        file.write(words_for_day.model_dump_json(indent=2))
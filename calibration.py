@@ -0,0 +1,30 @@
+from itertools import combinations
+
+from ai import embed, word_in_text
+from models import Word
+from vectors import cosine_similarity
+
+
+# Estimates how hard a word set is to guess from how unrelated its words are to each other: words that belong in the
+# same scene are easier to pick out of an image than ones that have nothing to do with each other. Roughly 0 (easy)
+# to 1 (hard).
+def score_difficulty(words: list[Word]) -> float:
+    embeddings = embed([word.word for word in words])
+    distances = [1 - cosine_similarity(a, b) for a, b in combinations(embeddings, 2)]
+    return round(sum(distances) / len(distances), 3)
+
+
+# The share of the words the vision model guessed from the image alone, see ai.guess_words
+def score_guessability(words: list[Word], guesses: list[str]) -> float:
+    guessed = [word for word in words if any(word_in_text(word.word, guess) for guess in guesses)]
+    return round(len(guessed) / len(words), 3)
+
+
+# An easy image nobody can read anything from, or a hard one that gives everything away, is worth a look before it's
+# published
+def check_guessability(difficulty: str, guessability: float) -> str | None:
+    if difficulty == "easy" and guessability == 0:
+        return "the vision model didn't guess any of the words"
+    if difficulty in ("hard", "dreaming") and guessability == 1:
+        return "the vision model guessed every word"
+    return None
@@ -0,0 +1,39 @@
+import re
+
+import ai
+from doctor import get_configured_models
+
+CAPABILITIES = ["chat", "image", "moderation", "embedding", "speech"]
+# Which capability a model is for, going by how the provider names them, most specific first. Anything that doesn't
+# match (fine tunes, transcription, realtime and so on) is left out unless all of them are asked for.
+CAPABILITY_PATTERNS = {
+    "speech": re.compile(r"tts"),
+    "image": re.compile(r"^(dall-e|gpt-image)"),
+    "moderation": re.compile(r"moderation"),
+    "embedding": re.compile(r"embedding"),
+    "chat": re.compile(r"^(gpt-|o\d|chatgpt-)(?!.*(audio|realtime|transcribe|search))"),
+}
+
+
+def get_capability(model: str) -> str | None:
+    for capability, pattern in CAPABILITY_PATTERNS.items():
+        if pattern.search(model):
+            return capability
+    return None
+
+
+# Meant for a terminal, so it's printed rather than logged. Models in the current config are marked with a *.
+def print_models(show_all: bool = False):
+    configured = get_configured_models()
+    by_capability: dict[str, list[str]] = {}
+    for model in ai.list_models():
+        capability = get_capability(model)
+        if capability is not None or show_all:
+            by_capability.setdefault(capability or "other", []).append(model)
+    print(f"Models available from {ai.get_provider()}")
+    for capability in [*CAPABILITIES, "other"]:
+        if capability not in by_capability:
+            continue
+        print(f"\n{capability}")
+        for model in by_capability[capability]:
+            print(f"  {'*' if model in configured else ' '} {model}")
@@ -1,36 +1,188 @@
+import json
 import os
+from uuid import uuid4
 
 import botocore
 import boto3
 import requests
+from boto3.exceptions import S3UploadFailedError
+from botocore.exceptions import BotoCoreError, ClientError
+
+import config
+import errors
 
 ENDPOINT_URL = "https://nyc3.digitaloceanspaces.com"
 CONFIG = botocore.config.Config(s3={"addressing_style": "virtual"})
 REGION = "nyc3"
-CDN_ACCESS_KEY_ID = os.environ["CDN_ACCESS_KEY_ID"]
-CDN_SECRET_ACCESS_KEY = os.environ["CDN_SECRET_ACCESS_KEY"]
 BUCKET = "iamdreamingof"
 CDN_BASE_URL = "https://cdn.iamdreamingof.com"
 
 
+def get_bucket() -> str:
+    return config.get("CDN_BUCKET", BUCKET)
+
+
+def get_base_url() -> str:
+    return config.get("CDN_BASE_URL", CDN_BASE_URL)
+
+
+# Put in front of every key, e.g. "env/staging/", so environments can share a bucket without touching each other's
+# files. Everything outside this module works with keys without it.
+def get_key_prefix() -> str:
+    return config.get("CDN_KEY_PREFIX", "")
+
+
+def get_object_key(key: str, prefix: str | None = None) -> str:
+    return f"{get_key_prefix() if prefix is None else prefix}{key}"
+
+
 def get_client():
     session = boto3.session.Session()
     return session.client(
         "s3",
-        endpoint_url=ENDPOINT_URL,
+        endpoint_url=config.get("CDN_ENDPOINT_URL", ENDPOINT_URL),
         config=CONFIG,
-        region_name=REGION,
-        aws_access_key_id=CDN_ACCESS_KEY_ID,
-        aws_secret_access_key=CDN_SECRET_ACCESS_KEY,
+        region_name=config.get("CDN_REGION", REGION),
+        aws_access_key_id=config.get_required("CDN_ACCESS_KEY_ID"),
+        aws_secret_access_key=config.get_required("CDN_SECRET_ACCESS_KEY"),
     )
 
 
-def upload_file(path: str, key: str) -> str:
+def get_url(key: str) -> str:
+    return f"{get_base_url()}/{get_object_key(key)}"
+
+
+def get_acl(public: bool) -> str:
+    return "public-read" if public else "private"
+
+
+def get_size(key: str, bucket: str | None = None, key_prefix: str | None = None) -> int | None:
+    client = get_client()
+    try:
+        return client.head_object(Bucket=bucket or get_bucket(), Key=get_object_key(key, key_prefix))["ContentLength"]
+    except ClientError as e:
+        if e.response.get("Error", {}).get("Code") in ("404", "NoSuchKey", "NotFound"):
+            return None
+        raise errors.from_s3_error(e, f"Failed to check {key}", key) from e
+    except BotoCoreError as e:
+        raise errors.from_s3_error(e, f"Failed to check {key}", key) from e
+
+
+# A WebP once went out truncated without the upload complaining, so every upload is checked against what was sent.
+# JSON is also fetched back through the CDN, the way the site will, to make sure it parses.
+def verify(key: str, size: int, public_json: bool = False):
+    if not config.get_bool("VERIFY_UPLOADS", True):
+        return
+    uploaded_size = get_size(key)
+    if uploaded_size != size:
+        raise errors.RetryableError(
+            f"Uploaded {key} is {uploaded_size} bytes instead of {size}",
+            provider="s3",
+            endpoint=key,
+        )
+    if public_json:
+        try:
+            requests.get(f"{get_url(key)}?id={uuid4()}", timeout=30).json()
+        except (requests.RequestException, ValueError) as e:
+            raise errors.RetryableError(
+                f"Uploaded {key} can't be read back: {e}", provider="cdn", endpoint=key
+            ) from e
+
+
+def upload_file(path: str, key: str, public: bool = True) -> str:
+    client = get_client()
+    try:
+        client.upload_file(path, get_bucket(), get_object_key(key), ExtraArgs={"ACL": get_acl(public)})
+    except (BotoCoreError, ClientError, S3UploadFailedError) as e:
+        raise errors.from_s3_error(e, f"Failed to upload {key}", key) from e
+    verify(key, os.path.getsize(path))
+    return get_url(key)
+
+
+def upload_bytes(data: bytes, key: str, content_type: str, public: bool = True) -> str:
+    client = get_client()
+    try:
+        client.put_object(
+            Body=data,
+            Bucket=get_bucket(),
+            Key=get_object_key(key),
+            ACL=get_acl(public),
+            ContentType=content_type,
+        )
+    except (BotoCoreError, ClientError) as e:
+        raise errors.from_s3_error(e, f"Failed to upload {key}", key) from e
+    verify(key, len(data), public and content_type == "application/json")
+    return get_url(key)
+
+
+# Asks the bucket rather than the CDN, so a day published moments ago isn't missed because of the edge cache
+def exists(key: str) -> bool:
+    return get_size(key) is not None
+
+
+def upload_json(data: str, key: str, public: bool = True) -> str:
+    return upload_bytes(data.encode("utf-8"), key, "application/json", public)
+
+
+# Server side, so nothing is downloaded again. The content type comes along with the object. The source is under this
+# bucket's key prefix unless another is given.
+def copy(
+    source_key: str,
+    key: str,
+    public: bool = True,
+    source_bucket: str | None = None,
+    source_key_prefix: str | None = None,
+) -> str:
+    client = get_client()
+    try:
+        client.copy_object(
+            Bucket=get_bucket(),
+            Key=get_object_key(key),
+            CopySource={
+                "Bucket": source_bucket or get_bucket(),
+                "Key": get_object_key(source_key, source_key_prefix),
+            },
+            ACL=get_acl(public),
+        )
+    except (BotoCoreError, ClientError) as e:
+        raise errors.from_s3_error(e, f"Failed to copy {source_key} to {key}", key) from e
+    verify(key, get_size(source_key, source_bucket, source_key_prefix), public and key.endswith(".json"))
+    return get_url(key)
+
+
+# Yields every key under the prefix along with when it was last modified
+def list_keys(prefix: str = ""):
+    client = get_client()
+    try:
+        for page in client.get_paginator("list_objects_v2").paginate(
+            Bucket=get_bucket(), Prefix=get_object_key(prefix)
+        ):
+            for item in page.get("Contents", []):
+                yield item["Key"].removeprefix(get_key_prefix()), item["LastModified"]
+    except (BotoCoreError, ClientError) as e:
+        raise errors.from_s3_error(e, f"Failed to list {prefix or 'the bucket'}", prefix) from e
+
+
+def delete(keys: list[str]):
+    client = get_client()
+    try:
+        client.delete_objects(
+            Bucket=get_bucket(), Delete={"Objects": [{"Key": get_object_key(key)} for key in keys]}
+        )
+    except (BotoCoreError, ClientError) as e:
+        raise errors.from_s3_error(e, f"Failed to delete {len(keys)} files", keys[0]) from e
+
+
+# Straight from the bucket, for ones that aren't served publicly
+def read_json(key: str, bucket: str | None = None, key_prefix: str | None = None):
     client = get_client()
-    client.upload_file(path, BUCKET, key, ExtraArgs={"ACL": "public-read"})
-    return f"{CDN_BASE_URL}/{key}"
+    try:
+        response = client.get_object(Bucket=bucket or get_bucket(), Key=get_object_key(key, key_prefix))
+    except (BotoCoreError, ClientError) as e:
+        raise errors.from_s3_error(e, f"Failed to read {key}", key) from e
+    return json.loads(response["Body"].read())
 
 
 # TODO: This is easier, but this is hitting the CDN's edge cache, which means it's not always up to date. Switch to hit the origin direectly.
 def read_public_json(path: str) -> str:
-    return requests.get(f"{CDN_BASE_URL}/{path}").json()
+    return requests.get(get_url(path)).json()
@@ -0,0 +1,139 @@
+import os
+import tomllib
+import typing
+
+import errors
+import secretstore
+
+CONFIG_PATH_ENV = "GENERATOR_CONFIG"
+PROFILE_ENV = "GENERATOR_PROFILE"
+DEFAULT_CONFIG_PATH = "generator.toml"
+# Settings that only make sense as a whole number of at least one, e.g. no image attempts at all can't make a challenge
+POSITIVE_INTEGERS = ["IMAGE_ATTEMPTS"]
+
+# Values from the config file, keyed the same way as their environment variables, i.e. `chat_model = "gpt-4"` is
+# CHAT_MODEL and `[dreaming] chat_model = "..."` is DREAMING_CHAT_MODEL. The selected `[profile.<name>]` table is
+# layered on top of the top level values.
+file_values: dict[str, typing.Any] = {}
+# Values from command line flags, which win over the environment and the config file
+overrides: dict[str, typing.Any] = {}
+
+
+def flatten(table: dict, prefix: str = "") -> dict[str, typing.Any]:
+    values = {}
+    for key, value in table.items():
+        name = f"{prefix}{key}".upper()
+        if isinstance(value, dict):
+            values.update(flatten(value, f"{name}_"))
+        else:
+            values[name] = value
+    return values
+
+
+def load(path: str | None = None, profile: str | None = None):
+    path = path or os.environ.get(CONFIG_PATH_ENV)
+    profile = profile or os.environ.get(PROFILE_ENV)
+    if path is None and os.path.exists(DEFAULT_CONFIG_PATH):
+        path = DEFAULT_CONFIG_PATH
+
+    file_values.clear()
+    if path is None:
+        if profile:
+            raise errors.ConfigError(f"Profile {profile} was requested but there is no config file")
+    else:
+        load_file(path, profile)
+
+    resolve_secrets()
+    validate()
+
+
+def load_file(path: str, profile: str | None):
+    try:
+        with open(path, "rb") as file:
+            table = tomllib.load(file)
+    except tomllib.TOMLDecodeError as e:
+        raise errors.ConfigError(f"Failed to parse {path}: {e}") from e
+    except OSError as e:
+        raise errors.ConfigError(f"Failed to read {path}: {e}") from e
+    profiles = table.pop("profile", {})
+    file_values.update(flatten(table))
+
+    if profile:
+        if profile not in profiles:
+            raise errors.ConfigError(
+                f"Unknown profile {profile} in {path}, expected one of: {', '.join(profiles) or 'none'}"
+            )
+        file_values.update(flatten(profiles[profile]))
+
+
+# Secret references are resolved up front so a bad path fails the run before anything is generated
+def resolve_secrets():
+    for key in set(file_values) | set(os.environ):
+        get(key)
+
+
+def validate():
+    for key in POSITIVE_INTEGERS:
+        value = get(key)
+        if value is None:
+            continue
+        try:
+            number = int(value)
+        except (TypeError, ValueError):
+            number = 0
+        if number < 1:
+            raise errors.ConfigError(f"{key} has to be a whole number of at least 1, not {value!r}")
+
+
+def set_override(key: str, value: typing.Any):
+    overrides[key] = value
+
+
+# Environment variables always win over the config file
+def get(key: str, default: typing.Any = None) -> typing.Any:
+    if key in overrides:
+        return overrides[key]
+    if key in os.environ:
+        return secretstore.resolve(os.environ[key])
+    return secretstore.resolve(file_values.get(key, default))
+
+
+def get_required(key: str) -> typing.Any:
+    value = get(key)
+    if value is None:
+        raise errors.ConfigError(f"Missing required config value {key}")
+    return value
+
+
+def parse_bool(value: typing.Any, default: bool = False) -> bool:
+    if value is None:
+        return default
+    if isinstance(value, str):
+        return value.strip().lower() in ("1", "true", "yes", "on")
+    return bool(value)
+
+
+def get_bool(key: str, default: bool = False) -> bool:
+    return parse_bool(get(key), default)
+
+
+# Lists are comma separated in the environment, but can be real arrays in the config file
+def get_list(key: str, default: list | None = None) -> list:
+    value = get(key)
+    if value is None:
+        return default or []
+    if isinstance(value, str):
+        return [item.strip() for item in value.split(",") if item.strip()]
+    return list(value)
+
+
+def get_for_difficulty(key: str, difficulty: str | None, default: typing.Any = None) -> typing.Any:
+    if difficulty is not None:
+        value = get(f"{difficulty.upper()}_{key}")
+        if value is not None:
+            return value
+    return get(key, default)
+
+
+def get_bool_for_difficulty(key: str, difficulty: str | None, default: bool = False) -> bool:
+    return parse_bool(get_for_difficulty(key, difficulty), default)
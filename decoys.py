@@ -0,0 +1,62 @@
+import logging
+import math
+import random
+
+import config
+from ai import embed
+from models import Word
+from vectors import cosine_similarity
+from words import load_words_of_type
+
+DECOY_COUNT = 6
+CANDIDATES_PER_WORD = 15
+# Too similar and a decoy is arguably a right answer, too far and it's obviously wrong
+DECOY_MIN_SIMILARITY = 0.2
+DECOY_MAX_SIMILARITY = 0.55
+
+logger = logging.getLogger(__name__)
+
+
+def pick_decoys_for_word(
+    word: Word,
+    word_embedding: list[float],
+    candidates: list[tuple[str, list[float]]],
+    count: int,
+) -> list[Word]:
+    low = float(config.get("DECOY_MIN_SIMILARITY", DECOY_MIN_SIMILARITY))
+    high = float(config.get("DECOY_MAX_SIMILARITY", DECOY_MAX_SIMILARITY))
+    middle = (low + high) / 2
+
+    # Candidates inside the band come first, anything else only fills in if the band runs dry
+    scored = [(cosine_similarity(word_embedding, embedding), candidate) for candidate, embedding in candidates]
+    scored.sort(key=lambda item: (not low <= item[0] <= high, abs(item[0] - middle)))
+    return [Word(word=candidate, type=word.type) for _, candidate in scored[:count]]
+
+
+def generate_decoys(words: list[Word], excluded: set[str]) -> list[Word]:
+    decoy_count = int(config.get("DECOY_COUNT", DECOY_COUNT))
+    if decoy_count == 0:
+        return []
+    per_word = math.ceil(decoy_count / len(words))
+
+    candidates_by_word = []
+    for word in words:
+        pool = [candidate for candidate in load_words_of_type(word.type) if candidate not in excluded]
+        candidates_by_word.append(random.sample(pool, min(CANDIDATES_PER_WORD, len(pool))))
+
+    texts = [word.word for word in words] + [
+        candidate for candidates in candidates_by_word for candidate in candidates
+    ]
+    embeddings = embed(texts)
+    word_embeddings = embeddings[: len(words)]
+    candidate_embeddings = iter(embeddings[len(words):])
+
+    decoys = []
+    for word, word_embedding, candidates in zip(words, word_embeddings, candidates_by_word):
+        embedded_candidates = [(candidate, next(candidate_embeddings)) for candidate in candidates]
+        for decoy in pick_decoys_for_word(word, word_embedding, embedded_candidates, per_word):
+            if decoy.word not in {existing.word for existing in decoys}:
+                decoys.append(decoy)
+
+    random.shuffle(decoys)
+    return decoys[:decoy_count]
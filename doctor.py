@@ -0,0 +1,89 @@
+import typing
+from uuid import uuid4
+
+import ai
+import cdn
+import config
+import signing
+
+# Everything a scheduled run needs before it gets anywhere near generating a day
+REQUIRED_KEYS = [
+    "AI_API_KEY",
+    "CDN_ACCESS_KEY_ID",
+    "CDN_SECRET_ACCESS_KEY",
+    "LOGTAIL_SOURCE_TOKEN",
+    "ROLLBAR_ACCESS_TOKEN",
+    "ROLLBAR_ENVIRONMENT",
+    "HONEYBADGER_API_KEY",
+    "HONEYBADGER_CHECKIN_ID",
+]
+DOCTOR_PREFIX = "doctor"
+
+
+def check_required_config() -> str:
+    missing = [key for key in REQUIRED_KEYS if config.get(key) is None]
+    if missing:
+        raise ValueError(f"Missing {', '.join(missing)}")
+    return f"{len(REQUIRED_KEYS)} keys set"
+
+
+# Listing the models is the cheapest call that needs a valid key
+def check_api_key() -> str:
+    ai.list_models()
+    return ai.get_provider()
+
+
+def get_configured_models() -> set[str]:
+    models = set()
+    for difficulty in [None, "easy", "medium", "hard", "dreaming"]:
+        models.update(ai.get_models(difficulty).values())
+    return models
+
+
+# A small private file, uploaded, checked and deleted again
+def check_cdn() -> str:
+    key = f"{DOCTOR_PREFIX}/{uuid4()}.txt"
+    data = b"doctor"
+    cdn.upload_bytes(data, key, "text/plain", public=False)
+    try:
+        size = cdn.get_size(key)
+        if size != len(data):
+            raise ValueError(f"Uploaded {len(data)} bytes to {key}, the bucket has {size}")
+    finally:
+        cdn.delete([key])
+    return f"round trip to {cdn.get_bucket()}/{cdn.get_key_prefix()}"
+
+
+def check_signing_key() -> str:
+    if signing.sign("doctor") is None:
+        return "not set, days won't be signed"
+    return "valid"
+
+
+def get_checks() -> list[tuple[str, typing.Callable[[], str]]]:
+    checks = [
+        ("Required config", check_required_config),
+        ("AI API key", check_api_key),
+    ]
+    for model in sorted(get_configured_models()):
+        checks.append((f"Model {model}", lambda model=model: ai.check_model(model) or "available"))
+    checks += [
+        ("CDN credentials", check_cdn),
+        ("Day signing key", check_signing_key),
+    ]
+    return checks
+
+
+# Meant for a terminal, so it's printed rather than logged. Returns whether everything passed.
+def run_checks() -> bool:
+    passed = True
+    print(f"{'Check':<40}{'Result':<8}Detail")
+    for name, check in get_checks():
+        try:
+            detail, result = check(), "ok"
+        except Exception as e:
+            detail, result = str(e), "FAILED"
+            passed = False
+        print(f"{name:<40}{result:<8}{detail}")
+    print("Ready" if passed else "Not ready")
+    return passed
@@ -0,0 +1,141 @@
+import requests
+from boto3.exceptions import S3UploadFailedError
+from botocore.exceptions import BotoCoreError, ClientError, EndpointConnectionError
+
+# Anything that isn't a FatalError is worth another attempt, so only the things a retry can't fix (a bad API key,
+# missing config, a request the provider rejects outright) need to be marked fatal
+RETRYABLE_STATUSES = {408, 409, 429}
+FATAL_S3_CODES = {
+    "AccessDenied",
+    "InvalidAccessKeyId",
+    "NoSuchBucket",
+    "SignatureDoesNotMatch",
+}
+
+
+# Carries what failed as fields rather than only in the message, so logs and alerts can be grouped by them
+class GeneratorError(Exception):
+    def __init__(
+        self,
+        message: str,
+        provider: str | None = None,
+        endpoint: str | None = None,
+        status: int | str | None = None,
+    ):
+        super().__init__(message)
+        self.provider = provider
+        self.endpoint = endpoint
+        self.status = status
+        # Filled in by the run once the error reaches it
+        self.attempt: int | None = None
+
+    def context(self) -> dict:
+        return {
+            "category": "retryable" if isinstance(self, RetryableError) else "fatal",
+            "provider": self.provider,
+            "endpoint": self.endpoint,
+            "status": self.status,
+            "attempt": self.attempt,
+        }
+
+
+class RetryableError(GeneratorError):
+    pass
+
+
+class FatalError(GeneratorError):
+    pass
+
+
+# Config that's missing or can't be used
+class ConfigError(FatalError):
+    pass
+
+
+# Arguments argparse can't check on its own, e.g. a --date before the site launched
+class ArgumentError(FatalError):
+    pass
+
+
+# The provider answered, but never with anything that passed our checks on it
+class QualityError(RetryableError):
+    pass
+
+
+# The image provider's safety system refused the prompt
+class RejectedPromptError(QualityError):
+    pass
+
+
+# So whatever runs the generator can tell failures apart, e.g. try again later when the provider is having a bad day
+# but get someone to look at missing config. 2 is what argparse exits with for bad arguments.
+EXIT_FAILED = 1
+EXIT_ARGUMENTS = 2
+EXIT_CONFIG = 3
+EXIT_PROVIDER = 4
+EXIT_CDN = 5
+EXIT_QUALITY = 6
+
+
+def get_exit_code(error: Exception) -> int:
+    if isinstance(error, ArgumentError):
+        return EXIT_ARGUMENTS
+    if isinstance(error, ConfigError):
+        return EXIT_CONFIG
+    if isinstance(error, QualityError):
+        return EXIT_QUALITY
+    if isinstance(error, GeneratorError) and error.provider == "s3":
+        return EXIT_CDN
+    if isinstance(error, GeneratorError) and error.provider is not None:
+        return EXIT_PROVIDER
+    return EXIT_FAILED
+
+
+def get_context(error: Exception) -> dict:
+    if isinstance(error, GeneratorError):
+        return error.context()
+    return {}
+
+
+# e.g. "fatal, provider api.openai.com, endpoint images/generations, status 400, attempt 1"
+def describe_context(context: dict) -> str:
+    return ", ".join(
+        f"{key} {value}" if key != "category" else value
+        for key, value in context.items()
+        if value is not None
+    )
+
+
+def is_retryable_status(status_code: int) -> bool:
+    return status_code in RETRYABLE_STATUSES or status_code >= 500
+
+
+def from_response(
+    response: requests.Response, message: str, provider: str, endpoint: str
+) -> GeneratorError:
+    error = RetryableError if is_retryable_status(response.status_code) else FatalError
+    return error(
+        f"{message}: {response.status_code} {response.text}",
+        provider=provider,
+        endpoint=endpoint,
+        status=response.status_code,
+    )
+
+
+def from_s3_error(error: Exception, message: str, key: str) -> GeneratorError:
+    # The transfer manager wraps the ClientError that caused a failed upload
+    if isinstance(error, S3UploadFailedError) and isinstance(error.__context__, ClientError):
+        error = error.__context__
+    if isinstance(error, ClientError):
+        code = error.response.get("Error", {}).get("Code")
+        fatal = code in FATAL_S3_CODES
+        return (FatalError if fatal else RetryableError)(
+            f"{message}: {code}",
+            provider="s3",
+            endpoint=f"{error.operation_name} {key}",
+            status=error.response.get("ResponseMetadata", {}).get("HTTPStatusCode") or code,
+        )
+    # Other than losing the connection, botocore raising on its own is about how the client was set up
+    if isinstance(error, BotoCoreError) and not isinstance(error, EndpointConnectionError):
+        return FatalError(f"{message}: {error}", provider="s3", endpoint=key)
+    return RetryableError(f"{message}: {error}", provider="s3", endpoint=key)
@@ -0,0 +1,87 @@
+import base64
+import hashlib
+import json
+import os
+import re
+from urllib.request import urlretrieve
+
+import requests
+
+import config
+import errors
+
+# PROVIDER_FIXTURES=record saves every provider response under PROVIDER_FIXTURES_DIR, keyed by the request, and
+# PROVIDER_FIXTURES=replay serves them back without touching the network
+FIXTURES_DIR = "fixtures"
+# Responses that aren't text, kept base64 encoded in the fixture
+BINARY_PATHS = {"audio/speech"}
+
+
+def get_mode() -> str | None:
+    return config.get("PROVIDER_FIXTURES")
+
+
+def get_fixture_path(path: str, data: dict) -> str:
+    request_hash = hashlib.sha256(
+        f"{path}\n{json.dumps(data, sort_keys=True)}".encode("utf-8")
+    ).hexdigest()
+    name = f'{re.sub(r"[^a-z0-9]+", "-", path)}-{request_hash[:16]}.json'
+    return os.path.join(config.get("PROVIDER_FIXTURES_DIR", FIXTURES_DIR), name)
+
+
+def to_response(status_code: int, body: str | bytes) -> requests.Response:
+    response = requests.Response()
+    response.status_code = status_code
+    response._content = body if isinstance(body, bytes) else body.encode("utf-8")
+    response._content_consumed = True
+    response.encoding = "utf-8"
+    return response
+
+
+# Generated image URLs expire after an hour, so the images themselves are kept next to the fixture
+def localize_image_urls(fixture_path: str, body: str) -> str:
+    try:
+        payload = json.loads(body)
+    except ValueError:
+        return body
+    for index, item in enumerate(payload.get("data") or []):
+        if isinstance(item, dict) and "url" in item:
+            image_path = os.path.abspath(f"{fixture_path.removesuffix('.json')}-{index}.png")
+            urlretrieve(item["url"], image_path)
+            item["url"] = f"file://{image_path}"
+    return json.dumps(payload)
+
+
+def record(path: str, data: dict, response: requests.Response) -> requests.Response:
+    fixture_path = get_fixture_path(path, data)
+    os.makedirs(os.path.dirname(fixture_path), exist_ok=True)
+    if response.ok and path in BINARY_PATHS:
+        with open(fixture_path, "w") as file:
+            json.dump(
+                {
+                    "path": path,
+                    "status": response.status_code,
+                    "body": base64.b64encode(response.content).decode("ascii"),
+                    "encoding": "base64",
+                },
+                file,
+                indent=2,
+            )
+        return to_response(response.status_code, response.content)
+    body = response.text
+    if response.ok and path == "images/generations":
+        body = localize_image_urls(fixture_path, body)
+    with open(fixture_path, "w") as file:
+        json.dump({"path": path, "status": response.status_code, "body": body}, file, indent=2)
+    return to_response(response.status_code, body)
+
+
+def replay(path: str, data: dict) -> requests.Response:
+    fixture_path = get_fixture_path(path, data)
+    if not os.path.exists(fixture_path):
+        raise errors.ConfigError(f"No recorded fixture for {path} at {fixture_path}")
+    with open(fixture_path) as file:
+        fixture = json.load(file)
+    if fixture.get("encoding") == "base64":
+        return to_response(fixture["status"], base64.b64decode(fixture["body"]))
+    return to_response(fixture["status"], fixture["body"])
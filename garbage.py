@@ -0,0 +1,81 @@
+import logging
+import re
+from datetime import datetime, timedelta, timezone
+
+import cdn
+import config
+import errors
+import keys
+from migrations import fetch_day, migrate_days, read_fresh_json
+
+# Images uploaded more recently than this are left alone, as they may belong to a run that hasn't updated the
+# indexes yet
+GC_MIN_AGE_HOURS = 24
+DELETE_BATCH_SIZE = 1000
+
+# What each placeholder in a key template can match, so only keys laid out like an image are considered
+PLACEHOLDER_PATTERNS = {
+    "date": r"\d{4}-\d{2}-\d{2}",
+    "year": r"\d{4}",
+    "month": r"\d{4}-\d{2}",
+}
+
+logger = logging.getLogger(__name__)
+
+
+def get_template_pattern(template: str) -> re.Pattern:
+    pattern = ""
+    for literal, placeholder in re.findall(r"([^{]*)(?:\{(\w+)\})?", template):
+        pattern += re.escape(literal)
+        if placeholder:
+            pattern += PLACEHOLDER_PATTERNS.get(placeholder, r"[^/]+")
+    return re.compile(pattern)
+
+
+# A URL under another base URL (one CDN_BASE_URL used to be, say) can't be matched to a key, and carrying on without it
+# would have its images deleted as orphans
+def get_key_for_url(url: str, base_url: str) -> str:
+    if not url.startswith(base_url):
+        raise errors.FatalError(f"{url} isn't under {base_url}, not collecting garbage until it's fixed")
+    return url.removeprefix(base_url)
+
+
+def get_referenced_keys() -> set[str]:
+    base_url = cdn.get_url("")
+    referenced = set()
+    for entry in migrate_days(read_fresh_json(keys.get_key("days"))).days:
+        for challenge in fetch_day(entry.date).challenges.present().values():
+            referenced.add(get_key_for_url(challenge.image_url_jpg, base_url))
+            referenced.add(get_key_for_url(challenge.image_url_webp, base_url))
+            # Narrations are next to the images with the default key templates
+            if challenge.narration_url:
+                referenced.add(get_key_for_url(challenge.narration_url, base_url))
+    return referenced
+
+
+# Images no published day points at, left behind by failed runs and regenerations. Every day has to be read
+# successfully first, so a day that can't be fetched never gets its images deleted.
+def find_orphaned_images() -> list[str]:
+    template = config.get("IMAGE_KEY_TEMPLATE", keys.KEY_TEMPLATES["image"])
+    pattern = get_template_pattern(template)
+    prefix = template.split("{", 1)[0]
+    cutoff = datetime.now(timezone.utc) - timedelta(
+        hours=float(config.get("GC_MIN_AGE_HOURS", GC_MIN_AGE_HOURS))
+    )
+    referenced = get_referenced_keys()
+    return [
+        key
+        for key, last_modified in cdn.list_keys(prefix)
+        if pattern.fullmatch(key) and key not in referenced and last_modified < cutoff
+    ]
+
+
+def collect_garbage(dry_run: bool = False) -> list[str]:
+    orphans = find_orphaned_images()
+    for key in orphans:
+        logger.info("%s %s", "Would delete" if dry_run else "Deleting", key)
+    if not dry_run:
+        for start in range(0, len(orphans), DELETE_BATCH_SIZE):
+            cdn.delete(orphans[start : start + DELETE_BATCH_SIZE])
+    logger.info("%s %s orphaned images", "Found" if dry_run else "Deleted", len(orphans))
+    return orphans
@@ -0,0 +1,53 @@
+import json
+import logging
+
+import config
+from ai import chat, format_words, word_in_text
+from models import Word, WordHints
+
+HINT_LEVELS = 3
+HINT_ATTEMPTS = 2
+
+logger = logging.getLogger(__name__)
+
+HINT_INSTRUCTIONS = """
+    You are writing hints for a guessing game where players guess the words behind a dream-like image.
+    You will be given words, each separated by a comma, with their type in brackets.
+    For each word write three hints that get progressively easier: a vague one, a medium one, and one that almost
+    gives the word away. A hint must never contain the word itself.
+    Respond with only a JSON object of the form {"hints": [{"word": "...", "hints": ["...", "...", "..."]}]},
+    with one entry per word in the order given.
+    """
+
+
+def parse_hints(content: str, words: list[Word]) -> list[WordHints]:
+    # Only the fields that are published, whatever else the model adds
+    hints = [WordHints(word=entry["word"], hints=entry["hints"]) for entry in json.loads(content)["hints"]]
+    if [entry.word for entry in hints] != [word.word for word in words]:
+        raise ValueError("Hints don't match the words they were asked for")
+    for entry in hints:
+        if len(entry.hints) != HINT_LEVELS:
+            raise ValueError(f"Expected {HINT_LEVELS} hints for {entry.word}")
+        if any(word_in_text(entry.word, hint) for hint in entry.hints):
+            raise ValueError(f"A hint gives away {entry.word}")
+    return hints
+
+
+# Hints are a nice to have, so a challenge is published without them rather than failing over them
+def generate_hints(words: list[Word], difficulty: str | None = None) -> list[WordHints]:
+    if not config.get_bool("HINTS_ENABLED", True):
+        return []
+
+    messages = [
+        {"role": "system", "content": HINT_INSTRUCTIONS},
+        {"role": "user", "content": format_words(words)},
+    ]
+    for _ in range(int(config.get("HINT_ATTEMPTS", HINT_ATTEMPTS))):
+        content = chat(messages, difficulty, json_response=True)
+        try:
+            return parse_hints(content, words)
+        except (KeyError, TypeError, ValueError) as e:
+            logger.info("Hints were unusable, asking again: %s", e)
+
+    logger.warning("Failed to generate hints, publishing without them")
+    return []
@@ -0,0 +1,42 @@
+from datetime import datetime
+
+import runstore
+
+HISTORY_LIMIT = 20
+
+
+def get_duration(started_at: str, finished_at: str | None) -> str:
+    if finished_at is None:
+        return "-"
+    seconds = (datetime.fromisoformat(finished_at) - datetime.fromisoformat(started_at)).total_seconds()
+    return f"{int(seconds // 60)}m{int(seconds % 60):02d}s"
+
+
+def get_result(run) -> str:
+    if run["finished_at"] is None:
+        return "unfinished"
+    return "succeeded" if run["succeeded"] else "failed"
+
+
+# Meant for a terminal, so it's printed rather than logged
+def print_runs(limit: int = HISTORY_LIMIT, failed_only: bool = False):
+    runs = runstore.list_runs(limit, failed_only)
+    if not runs:
+        print("No runs recorded")
+        return
+    print(f"{'Run':<10}{'Command':<9}{'Date':<12}{'Started':<21}{'Duration':<10}{'Result':<12}{'Attempts':<10}Error")
+    for run in runs:
+        print(
+            f"{run['run_id']:<10}{run['command']:<9}{run['date']:<12}{run['started_at'][:19]:<21}"
+            f"{get_duration(run['started_at'], run['finished_at']):<10}{get_result(run):<12}"
+            f"{run['attempts']:<10}{run['error'] or ''}"
+        )
+
+
+def print_run(run_id: str) -> bool:
+    report = runstore.get_report(run_id)
+    if report is None:
+        print(f"No run {run_id} recorded")
+        return False
+    print(report.to_markdown(), end="")
+    return True
@@ -1,41 +1,149 @@
+import hashlib
+import os
 from uuid import uuid4
 
-from pydantic import BaseModel
+from pydantic import BaseModel, Field
+from wand.color import Color
+from wand.drawing import Drawing
 from wand.image import Image
 
+SHARE_IMAGE_WIDTH = 1200
+SHARE_IMAGE_HEIGHT = 630
+WEB_IMAGE_SIZE = 800
+# For spotting images that aren't worth publishing, see find_image_problem. Deviations are a fraction of the full range.
+CHECK_IMAGE_SIZE = 256
+BLANK_MAX_DEVIATION = 0.02
+NOISE_MAX_BLURRED_RATIO = 0.3
+CONTENT_TYPES = {
+    "jpg": "image/jpeg",
+    "webp": "image/webp",
+}
+
+
+# One encoded rendition, kept in memory so nothing has to read a file back to upload it or learn about it. It's only
+# written out (path) if generate_images_for_web was given somewhere to put it.
+class WebImage(BaseModel):
+    path: str | None = None
+    filename: str
+    format: str
+    content_type: str
+    width: int
+    height: int
+    bytes: int
+    sha256: str
+    data: bytes = Field(exclude=True, repr=False)
+
 
 class ImagesForWeb(BaseModel):
-    jpeg_path: str
-    jpeg_filename: str
-    webp_path: str
-    webp_filename: str
+    jpeg: WebImage
+    webp: WebImage
 
 
-def generate_images_for_web(filename: str) -> ImagesForWeb:
-    jpeg_path = None
-    webp_path = None
-    jpeg_filename = None
-    webp_filename = None
+def generate_images_for_web(
+    filename: str, output_dir: str | None = None, size: int = WEB_IMAGE_SIZE
+) -> ImagesForWeb:
+    renditions = {}
     output_uuid = str(uuid4())
 
     with Image(filename=filename) as img:
         for file_format in ["jpg", "webp"]:
             with img.clone() as i:
                 output_name = f"{output_uuid}.{file_format}"
-                output_path = f"/tmp/output_name"
-                i.resize(800, 800)
+                i.resize(size, size)
                 i.format = file_format
-                i.save(filename=output_path)
-                if file_format == "jpg":
-                    jpeg_path = output_path
-                    jpeg_filename = output_name
-                else:
-                    webp_path = output_path
-                    webp_filename = output_name
-
-    return ImagesForWeb(
-        jpeg_path=jpeg_path,
-        webp_path=webp_path,
-        jpeg_filename=jpeg_filename,
-        webp_filename=webp_filename,
-    )
+                data = i.make_blob()
+                output_path = None
+                if output_dir is not None:
+                    output_path = os.path.join(output_dir, output_name)
+                    with open(output_path, "wb") as file:
+                        file.write(data)
+                renditions[file_format] = WebImage(
+                    path=output_path,
+                    filename=output_name,
+                    format=file_format,
+                    content_type=CONTENT_TYPES[file_format],
+                    width=i.width,
+                    height=i.height,
+                    bytes=len(data),
+                    sha256=hashlib.sha256(data).hexdigest(),
+                    data=data,
+                )
+
+    return ImagesForWeb(jpeg=renditions["jpg"], webp=renditions["webp"])
+
+
+# Providers occasionally return a flat canvas (e.g. all gray when they're under load) or static instead of a picture.
+# A flat image barely deviates from its mean, and blurring static takes away nearly all of its deviation where a real
+# picture keeps most of it. Returns what's wrong, or None if the image looks like a picture.
+def find_image_problem(path: str) -> str | None:
+    with Image(filename=path) as img:
+        img.transform_colorspace("gray")
+        img.resize(CHECK_IMAGE_SIZE, CHECK_IMAGE_SIZE)
+        deviation = img.standard_deviation / img.quantum_range
+        if deviation < BLANK_MAX_DEVIATION:
+            return f"it's a single color (deviation {deviation:.3f})"
+        with img.clone() as blurred:
+            blurred.blur(radius=0, sigma=2)
+            ratio = blurred.standard_deviation / img.standard_deviation
+        if ratio < NOISE_MAX_BLURRED_RATIO:
+            return f"it's noise (blurring keeps {ratio:.2f} of its deviation)"
+    return None
+
+
+# Renditions bigger than the provider's image come out soft with a plain resize, so this enlarges with a Lanczos filter
+# and sharpens it a little before they're made
+def upscale_image(path: str, size: int, output_path: str):
+    with Image(filename=path) as img:
+        if img.width < size:
+            img.resize(size, size, filter="lanczos")
+            img.unsharp_mask(radius=0, sigma=1, amount=0.6, threshold=0.02)
+        img.save(filename=output_path)
+
+
+def get_sha256(path: str) -> str:
+    with open(path, "rb") as file:
+        return hashlib.sha256(file.read()).hexdigest()
+
+
+# Composes the day's four images into a 2x2 Open Graph card, with the date on a band across the bottom
+def generate_share_image(
+    image_paths: list[str | None], date: str, output_path: str, font_path: str | None
+):
+    cell_width = SHARE_IMAGE_WIDTH // 2
+    cell_height = SHARE_IMAGE_HEIGHT // 2
+    band_height = 70
+
+    with Image(
+        width=SHARE_IMAGE_WIDTH, height=SHARE_IMAGE_HEIGHT, background=Color("black")
+    ) as canvas:
+        # A missing challenge leaves its cell black rather than shifting the others
+        for index, path in enumerate(image_paths):
+            if path is None:
+                continue
+            with Image(filename=path) as cell:
+                cell.resize(cell_width, cell_width)
+                cell.crop(width=cell_width, height=cell_height, gravity="center")
+                canvas.composite(
+                    cell,
+                    left=(index % 2) * cell_width,
+                    top=(index // 2) * cell_height,
+                )
+
+        with Drawing() as draw:
+            draw.fill_color = Color("rgba(0, 0, 0, 0.6)")
+            draw.rectangle(
+                left=0,
+                top=SHARE_IMAGE_HEIGHT - band_height,
+                width=SHARE_IMAGE_WIDTH,
+                height=band_height,
+            )
+            draw.fill_color = Color("white")
+            if font_path:
+                draw.font = font_path
+            draw.font_size = 40
+            draw.text_alignment = "center"
+            draw.text(SHARE_IMAGE_WIDTH // 2, SHARE_IMAGE_HEIGHT - 22, date)
+            draw(canvas)
+
+        canvas.format = "jpg"
+        canvas.save(filename=output_path)
@@ -0,0 +1,81 @@
+import hashlib
+import logging
+from datetime import datetime, timezone
+
+import cdn
+import keys
+from models import DateEntry, Day, Days, DaysManifest, ShardEntry
+
+
+logger = logging.getLogger(__name__)
+
+
+def get_shard_key(year: int) -> str:
+    return keys.get_key("days_shard", year=year)
+
+
+def get_year(date: str) -> int:
+    return int(date[:4])
+
+
+# A day generated ahead of time is kept out of the archive, sitemap and stats until its publish_at
+def is_live(publish_at: datetime | None) -> bool:
+    return publish_at is None or publish_at <= datetime.now(timezone.utc)
+
+
+# The entry for a day that's just been published, hashing the same JSON upload_day publishes
+def build_date_entry(for_day: Day) -> DateEntry:
+    return DateEntry(
+        date=for_day.date,
+        id=for_day.id,
+        sha256=hashlib.sha256(for_day.model_dump_json().encode("utf-8")).hexdigest(),
+        updated_at=datetime.now(timezone.utc),
+        publish_at=for_day.publish_at,
+    )
+
+
+# Adds the day's entry, or replaces the one it already has
+def set_date_entry(days: Days, for_day: Day):
+    entry = build_date_entry(for_day)
+    for index, existing in enumerate(days.days):
+        if existing.date == for_day.date:
+            days.days[index] = entry
+            return
+    days.days.append(entry)
+
+
+# days.json keeps growing, so the index is also split into one file per year with a small manifest pointing at them.
+# The combined days.json is still published while the frontend moves over.
+def build_shards(days: Days) -> dict[int, Days]:
+    shards: dict[int, Days] = {}
+    for entry in sorted(days.days, key=lambda entry: entry.date):
+        shards.setdefault(get_year(entry.date), Days(days=[])).days.append(entry)
+    return shards
+
+
+def build_manifest(shards: dict[int, Days]) -> DaysManifest:
+    return DaysManifest(
+        shards=[
+            ShardEntry(
+                year=year,
+                key=get_shard_key(year),
+                count=len(shard.days),
+                first_date=shard.days[0].date,
+                last_date=shard.days[-1].date,
+            )
+            for year, shard in sorted(shards.items())
+        ]
+    )
+
+
+# Returns the uploaded keys and their URLs
+def publish_shards(days: Days, years: list[int] | None = None) -> dict[str, str]:
+    shards = build_shards(days)
+    uploaded = {}
+    for year in years if years is not None else shards:
+        key = get_shard_key(year)
+        logger.info("Uploading %s", key)
+        uploaded[key] = cdn.upload_json(shards[year].model_dump_json(), key)
+    manifest_key = keys.get_key("days_manifest")
+    uploaded[manifest_key] = cdn.upload_json(build_manifest(shards).model_dump_json(), manifest_key)
+    return uploaded
@@ -0,0 +1,26 @@
+import config
+
+# Where everything is published, as str.format templates. Each can be overridden with <NAME>_KEY_TEMPLATE, e.g.
+# IMAGE_KEY_TEMPLATE = "images/{year}/{date}/{filename}". Templates that are given a date can also use {year} and
+# {month}.
+KEY_TEMPLATES = {
+    "image": "{date}/{filename}",
+    "share_image": "og/{date}.jpg",
+    "narration": "{date}/{filename}",
+    "day": "days/{date}.json",
+    "day_vectors": "days/{date}.vectors.json",
+    "today": "today.json",
+    "days": "days.json",
+    "days_shard": "days-{year}.json",
+    "days_manifest": "days-manifest.json",
+    "archive": "archive/{month}.json",
+    "sitemap": "sitemap.xml",
+    "stats": "stats.json",
+}
+
+
+def get_key(name: str, **values) -> str:
+    if "date" in values:
+        values.setdefault("year", values["date"][:4])
+        values.setdefault("month", values["date"][:7])
+    return config.get(f"{name.upper()}_KEY_TEMPLATE", KEY_TEMPLATES[name]).format(**values)
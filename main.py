@@ -1,166 +1,1333 @@
+import argparse
+import json
 import logging
 import os
+import random
+import shutil
 import sys
+import threading
+import time
 import typing
-from datetime import date
+from concurrent.futures import ThreadPoolExecutor, as_completed
+from datetime import datetime, timedelta, timezone
 from tempfile import NamedTemporaryFile
 from urllib.request import urlretrieve
 from uuid import uuid4
+from zoneinfo import ZoneInfo, ZoneInfoNotFoundError
 
 import requests
 import rollbar
-from honeybadger.contrib import HoneybadgerHandler
 from logtail import LogtailHandler
 from honeybadger import honeybadger
-from tenacity import retry, wait_fixed, stop_after_attempt
+from tenacity import retry, retry_if_not_exception_type, wait_fixed, stop_after_attempt
 
+import ai
+import archive
+import artifacts
+import catalog
 import cdn
-from ai import generate_prompt, generate_image
+import config
+import doctor
+import garbage
+import history
+import indexes
+import keys
+import notifications
+import novelty
+import progress
+import retention
+import runstore
+import schemas
+import signing
+import sitemap
+import stats
+import vectors
+from migrations import fetch_day, migrate_day, migrate_days, migrate_published, read_fresh_json
+from ai import (
+    choose_prompt_variant,
+    generate_day_title,
+    get_models,
+    get_provider,
+    generate_best_prompt,
+    generate_image,
+    generate_speech,
+    translate_prompt,
+    fit_image_prompt,
+    guess_words,
+    critique_image,
+    get_missing_words,
+    revise_prompt,
+    rewrite_prompt,
+    GeneratedPrompt,
+    ImageCritique,
+    PromptVariant,
+    PROMPT_TEMPLATE_VERSION,
+    IMAGE_TEMPLATE_VERSION,
+)
 from cdn import read_public_json
-from image import generate_images_for_web
-from models import Days, Challenge, Word, Challenges, Day, DateEntry
-from words import generate_words_for_day
+from artifacts import ChallengeArtifact
+from calibration import check_guessability, score_difficulty, score_guessability
+from decoys import generate_decoys
+from errors import (
+    EXIT_FAILED,
+    ArgumentError,
+    ConfigError,
+    FatalError,
+    GeneratorError,
+    QualityError,
+    RejectedPromptError,
+    get_context,
+    get_exit_code,
+)
+from hints import generate_hints
+from image import (
+    WEB_IMAGE_SIZE,
+    find_image_problem,
+    generate_images_for_web,
+    generate_share_image,
+    get_sha256,
+    upscale_image,
+)
+from models import (
+    Days,
+    Challenge,
+    Word,
+    Challenges,
+    Day,
+    Difficulty,
+    GenerationMeta,
+    DAY_ID_MAX,
+    SCHEMA_VERSION,
+    TemplateVersions,
+)
+from report import RunReport, TokenUsage, start_report
+from staging import Staging
+from styles import get_style_for_day
+from words import generate_word_list, generate_words_for_day
 
 DATE_FORMAT = "%Y-%m-%d"
+DIFFICULTIES = ["easy", "medium", "hard", "dreaming"]
+SHARE_IMAGE_FONT_PATH = "/usr/share/fonts/truetype/dejavu/DejaVuSerif.ttf"
+GENERATOR_VERSION = "0.1.0"
+BACKFILL_CONCURRENCY = 2
+IMAGE_ATTEMPTS = 2
+# How far ahead a day can be generated without --allow-future
+MAX_DAYS_AHEAD = 7
+LIBRARY_LOGGERS = ["boto3", "botocore", "s3transfer", "urllib3"]
 
-logtail_handler = LogtailHandler(source_token=os.environ["LOGTAIL_SOURCE_TOKEN"])
-honeybadger_handler = HoneybadgerHandler(api_key=os.environ["HONEYBADGER_API_KEY"])
 logging.basicConfig(stream=sys.stdout, level=logging.INFO)
 logger = logging.getLogger(__name__)
 logger.setLevel(logging.INFO)
-logger.handlers = []
-logger.addHandler(logtail_handler)
+# LOG_FILE's handler, kept from one site to the next while they share the file
+log_file_handler: logging.FileHandler | None = None
 
 
-rollbar.init(
-    access_token=os.environ["ROLLBAR_ACCESS_TOKEN"],
-    environment=os.environ["ROLLBAR_ENVIRONMENT"],
-    code_version="1.0",
-)
+# -q only leaves warnings and errors, -v turns on debug logs for the generator and -vv for the libraries it uses too
+def set_log_level(verbose: int, quiet: bool):
+    if quiet:
+        level = logging.WARNING
+    elif verbose:
+        level = logging.DEBUG
+    else:
+        level = logging.INFO
+    logging.getLogger().setLevel(level)
+    logger.setLevel(level)
+    for name in LIBRARY_LOGGERS:
+        logging.getLogger(name).setLevel(logging.DEBUG if verbose > 1 else max(level, logging.INFO))
+
+
+# On the root logger, so the file the failure email links to has every module's logs and not only this one's
+def set_log_file(path: str | None):
+    global log_file_handler
+    if log_file_handler is not None:
+        if path and log_file_handler.baseFilename == os.path.abspath(path):
+            return
+        logging.getLogger().removeHandler(log_file_handler)
+        log_file_handler.close()
+        log_file_handler = None
+    if path:
+        log_file_handler = logging.FileHandler(path)
+        logging.getLogger().addHandler(log_file_handler)
+
+
+# Logging and error reporting are set up once the config file has been loaded, as their keys can live there
+def setup():
+    logtail_handler = LogtailHandler(source_token=config.get_required("LOGTAIL_SOURCE_TOKEN"))
+    logger.handlers = []
+    logger.addHandler(logtail_handler)
+    set_log_file(config.get("LOG_FILE"))
+
+    rollbar.init(
+        access_token=config.get_required("ROLLBAR_ACCESS_TOKEN"),
+        environment=config.get_required("ROLLBAR_ENVIRONMENT"),
+        code_version="1.0",
+    )
+
+    honeybadger.configure(api_key=config.get_required("HONEYBADGER_API_KEY"))
 
-honeybadger.configure(api_key=os.environ["HONEYBADGER_API_KEY"])
 
 def check_in():
     logger.info("Checking in")
-    requests.get(f'https://api.honeybadger.io/v1/check_in/{os.environ["HONEYBADGER_CHECKIN_ID"]}')
+    requests.get(f'https://api.honeybadger.io/v1/check_in/{config.get_required("HONEYBADGER_CHECKIN_ID")}')
     logger.info("Checked in")
 
 
+# The day the site is on, which isn't the runner's day when e.g. it runs in UTC and the site's days start in New York
+def get_site_timezone() -> ZoneInfo | None:
+    name = config.get("SITE_TIMEZONE")
+    if not name:
+        return None
+    try:
+        return ZoneInfo(name)
+    except (ZoneInfoNotFoundError, ValueError):
+        raise ConfigError(f"SITE_TIMEZONE {name!r} is not a known timezone")
+
+
 def get_today_str() -> str:
-    return date.today().strftime(DATE_FORMAT)
+    return datetime.now(get_site_timezone()).strftime(DATE_FORMAT)
+
+
+# Catches typos in --date before they end up in days.json: the date has to be real and written in full. Used as the
+# argparse type, so a bad one exits with 2 like any other bad argument.
+def parse_date(value: str) -> str:
+    try:
+        parsed = datetime.strptime(value, DATE_FORMAT).date()
+    except ValueError:
+        parsed = None
+    if parsed is None or parsed.strftime(DATE_FORMAT) != value:
+        raise argparse.ArgumentTypeError(f"{value!r} is not a valid YYYY-MM-DD date")
+    return value
+
+
+# The checks on a date that need the config: not before SITE_LAUNCH_DATE and, for commands that create days, at most
+# MAX_DAYS_AHEAD days from now unless that's allowed
+def check_date(value: str, check_future: bool = False, allow_future: bool = False) -> str:
+    parsed = datetime.strptime(value, DATE_FORMAT).date()
+    launch_date = config.get("SITE_LAUNCH_DATE")
+    if launch_date and value < str(launch_date):
+        raise ArgumentError(f"{value} is before the site launched on {launch_date}")
+    max_days_ahead = int(config.get("MAX_DAYS_AHEAD", MAX_DAYS_AHEAD))
+    today = datetime.strptime(get_today_str(), DATE_FORMAT).date()
+    if check_future and (parsed - today).days > max_days_ahead:
+        logger.warning("%s is %s days from now", value, (parsed - today).days)
+        if not allow_future:
+            raise ArgumentError(
+                f"{value} is more than {max_days_ahead} days ahead, pass --allow-future to generate it anyway"
+            )
+    return value
+
+
+# When a day generated ahead of time goes live, None for today's and past days
+def get_publish_at(date_to_generate_for: str) -> datetime | None:
+    start = datetime.strptime(date_to_generate_for, DATE_FORMAT)
+    site_timezone = get_site_timezone()
+    start = start.replace(tzinfo=site_timezone) if site_timezone else start.astimezone()
+    if start <= datetime.now(timezone.utc):
+        return None
+    return start
+
+
+def upload_json(run_report: RunReport, data: str, key: str) -> str:
+    url = cdn.upload_json(data, key)
+    run_report.record_upload(key, url)
+    return url
+
+
+# Uploads (or stages) the day JSON, along with its signature if DAY_SIGNING_KEY is set
+def upload_day(for_day: Day, key: str, run_report: RunReport, staging: Staging | None = None):
+    for file_key, file_data in signing.get_signed_files(key, for_day.model_dump_json()).items():
+        if staging is not None:
+            staging.upload_json(file_data, file_key)
+        else:
+            upload_json(run_report, file_data, file_key)
+
+
+# Staged files are private and under their own prefix, so one that's left behind isn't a problem beyond the space
+def discard_staged(staging: Staging):
+    try:
+        staging.clean_up()
+    except Exception:
+        logger.exception("Failed to remove staged files under %s", staging.prefix)
+
+
+def promote_staged(staging: Staging, run_report: RunReport):
+    logger.info("Publishing staged files")
+    with run_report.stage("promote"):
+        for key, url in staging.promote().items():
+            run_report.record_upload(key, url)
+
+
+# Publishes the staged files and then the indexes, putting back what was there before if any of it fails. Meant to be
+# called holding publish_lock, so a roll back can't undo the index updates of a day published alongside.
+def publish_staged(staging: Staging, days: Days, for_day: Day, run_report: RunReport):
+    try:
+        promote_staged(staging, run_report)
+        # The indexes only change once everything they point at is there
+        publish_indexes(days, for_day, run_report, staging)
+    except Exception:
+        try:
+            staging.roll_back()
+        except Exception:
+            logger.exception("Failed to roll back what was published for %s", for_day.date)
+        raise
+    staging.finish()
+
+
+# With NOVELTY_CHECK on, a prompt too similar to a recent day's is regenerated. Repeating a motif isn't worth failing a
+# challenge over, so after NOVELTY_ATTEMPTS regenerations the last prompt is used anyway.
+def generate_novel_prompt(
+    words: list[Word],
+    date_to_generate_for: str,
+    difficulty: str,
+    prompt_variant: PromptVariant,
+    run_report: RunReport,
+) -> GeneratedPrompt:
+    attempts = 1 + int(config.get("NOVELTY_ATTEMPTS", novelty.NOVELTY_ATTEMPTS))
+    for _ in range(attempts):
+        logger.info("Generating prompt")
+        with run_report.stage("prompt", difficulty):
+            generated_prompt = generate_best_prompt(words, difficulty, prompt_variant.instructions)
+        if not config.get_bool("NOVELTY_CHECK"):
+            return generated_prompt
+        with run_report.stage("novelty", difficulty):
+            similar = novelty.find_similar_prompt(generated_prompt.description, date_to_generate_for)
+        if similar is None:
+            return generated_prompt
+        similarity, similar_prompt = similar
+        logger.warning("Prompt is %.2f similar to a recent one (%s)", similarity, similar_prompt)
+    logger.warning("Every %s prompt was close to a recent one, using the last", difficulty)
+    return generated_prompt
+
+
+# With IMAGE_CANDIDATES above 1, that many images are generated from the same prompt at once, downloaded next to the
+# challenge's image as candidate-<n>.png. Returns the paths of the ones that are usable, and the last problem found.
+def generate_candidates(
+    prompt: str,
+    style: str,
+    words: list[Word],
+    difficulty: str,
+    artifact_dir: str,
+    run_report: RunReport,
+    image_attempt: int,
+) -> tuple[list[str], str | None]:
+    count = int(config.get_for_difficulty("IMAGE_CANDIDATES", difficulty, 1))
+    if count > 1:
+        paths = [os.path.join(artifact_dir, f"candidate-{index}.png") for index in range(count)]
+    else:
+        paths = [artifacts.get_image_path(artifact_dir)]
+    with run_report.stage("image", difficulty), ThreadPoolExecutor(max_workers=len(paths)) as executor:
+        description = fit_image_prompt(prompt, style, words, difficulty)
+        generated_image_urls = list(executor.map(lambda _: generate_image(description, style, difficulty), paths))
+
+    logger.info("Downloading image")
+    with run_report.stage("download", difficulty):
+        for generated_image_url, path in zip(generated_image_urls, paths):
+            urlretrieve(generated_image_url, path)
+
+    usable = []
+    problem = None
+    with run_report.stage("check_image", difficulty):
+        for path in paths:
+            path_problem = find_image_problem(path)
+            run_report.record_qa(difficulty, "image", path_problem is None, path_problem, image_attempt)
+            if path_problem is None:
+                usable.append(path)
+            else:
+                problem = path_problem
+                logger.warning("Generated %s image is unusable, %s", difficulty, problem)
+    return usable, problem
+
+
+# Ranks candidate images by how many of the words the vision model recognizes in each, ties going to one whose
+# guessability suits the difficulty (with GUESSABILITY_CHECK on). Returns the best and its critique.
+def pick_image(
+    paths: list[str], words: list[Word], difficulty: str, run_report: RunReport
+) -> tuple[str, ImageCritique | None]:
+    def score(path: str) -> tuple[tuple[int, bool], ImageCritique | None]:
+        critique = critique_image(path, words, difficulty)
+        shown = len(words) - len(get_missing_words(critique, words)) if critique is not None else 0
+        suited = True
+        if config.get_bool_for_difficulty("GUESSABILITY_CHECK", difficulty):
+            guesses = guess_words(path, difficulty)
+            if guesses is not None:
+                suited = check_guessability(difficulty, score_guessability(words, guesses)) is None
+        return (shown, suited), critique
+
+    with run_report.stage("pick_image", difficulty), ThreadPoolExecutor(max_workers=len(paths)) as executor:
+        scores = list(executor.map(score, paths))
+    best = max(range(len(paths)), key=lambda index: scores[index][0])
+    logger.info(
+        "Picked %s of %s %s images, showing %s of the words",
+        os.path.basename(paths[best]),
+        len(paths),
+        difficulty,
+        scores[best][0][0],
+    )
+    return paths[best], scores[best][1]
+
+
+# Makes the challenge's image, artifact_dir/image.png, within IMAGE_ATTEMPTS attempts (IMAGE_ATTEMPT_DELAY seconds
+# apart). A blank or noisy image is always tried again. With IMAGE_CRITIQUE on, so is one the vision model doesn't see
+# every word in, and once the attempts run out it's used anyway, unless IMAGE_STRICT fails the challenge instead.
+# Returns the prompt the image was made from, which may have been revised along the way.
+def generate_challenge_image(
+    generated_prompt: GeneratedPrompt,
+    words: list[Word],
+    style: str,
+    difficulty: str,
+    artifact_dir: str,
+    run_report: RunReport,
+) -> GeneratedPrompt:
+    image_path = artifacts.get_image_path(artifact_dir)
+    attempts = int(config.get("IMAGE_ATTEMPTS", IMAGE_ATTEMPTS))
+    delay = float(config.get_for_difficulty("IMAGE_ATTEMPT_DELAY", difficulty, 0))
+    for attempt in range(attempts):
+        if attempt and delay:
+            logger.info("Waiting %s seconds before the next %s image", delay, difficulty)
+            time.sleep(delay)
+        logger.info("Generating image")
+        try:
+            usable, problem = generate_candidates(
+                generated_prompt.description, style, words, difficulty, artifact_dir, run_report, attempt + 1
+            )
+        except RejectedPromptError:
+            problem = "the prompt was rejected"
+            run_report.record_qa(difficulty, "rejected", False, problem, attempt + 1)
+            logger.warning("The image provider rejected the %s prompt, rewriting it", difficulty)
+            with run_report.stage("prompt", difficulty):
+                generated_prompt = rewrite_prompt(
+                    generated_prompt, words, "the image generator's safety system", difficulty
+                )
+            continue
+        if not usable:
+            continue
+
+        critique = None
+        if len(usable) > 1:
+            picked_path, critique = pick_image(usable, words, difficulty, run_report)
+        else:
+            picked_path = usable[0]
+        if picked_path != image_path:
+            shutil.copyfile(picked_path, image_path)
+        if not config.get_bool_for_difficulty("IMAGE_CRITIQUE", difficulty):
+            break
+
+        if critique is None:
+            with run_report.stage("critique", difficulty):
+                critique = critique_image(image_path, words, difficulty)
+        missing_words = get_missing_words(critique, words) if critique is not None else []
+        run_report.record_qa(
+            difficulty,
+            "critique",
+            not missing_words,
+            f"missing {', '.join(missing_words)}: {critique.critique}" if missing_words else None,
+            attempt + 1,
+        )
+        if not missing_words:
+            break
+        logger.warning(
+            "Generated %s image doesn't show %s: %s", difficulty, ", ".join(missing_words), critique.critique
+        )
+        if attempt == attempts - 1:
+            if config.get_bool_for_difficulty("IMAGE_STRICT", difficulty):
+                raise QualityError(
+                    f"The {difficulty} image still didn't show {', '.join(missing_words)} after {attempts} attempts",
+                    provider=get_provider(),
+                    endpoint="images/generations",
+                )
+            # An image that misses a word is still a usable image
+            logger.warning("Using the %s image anyway", difficulty)
+            break
+        with run_report.stage("prompt", difficulty):
+            generated_prompt = revise_prompt(generated_prompt, words, critique.critique, difficulty)
+    else:
+        raise QualityError(
+            f"Every generated {difficulty} image was unusable after {attempts} attempts, last time {problem}",
+            provider=get_provider(),
+            endpoint="images/generations",
+        )
+
+    return generated_prompt
 
 
 # Generates a challenge for a given list of words
-def create_challenge(words: list[Word], date_to_generate_for: str) -> Challenge:
-    logger.info("Generating prompt")
-    prompt = generate_prompt([word.word for word in words])
+# Everything that needs the providers: the prompt, hints, decoys, difficulty score and the image itself
+def generate_artifact(
+    words: list[Word],
+    date_to_generate_for: str,
+    difficulty: str,
+    style: str,
+    prompt_variant: PromptVariant,
+    excluded_words: set[str],
+    run_report: RunReport,
+    artifact_dir: str,
+) -> ChallengeArtifact:
+    generated_prompt = generate_novel_prompt(words, date_to_generate_for, difficulty, prompt_variant, run_report)
+
+    logger.info("Generating hints")
+    with run_report.stage("hints", difficulty):
+        hints = generate_hints(words, difficulty)
+
+    logger.info("Generating decoys")
+    with run_report.stage("decoys", difficulty):
+        decoys = generate_decoys(words, excluded_words)
+
+    logger.info("Scoring difficulty")
+    with run_report.stage("calibration", difficulty):
+        difficulty_score = score_difficulty(words)
+    logger.info("Difficulty score for %s is %s", difficulty, difficulty_score)
+
+    generated_prompt = generate_challenge_image(generated_prompt, words, style, difficulty, artifact_dir, run_report)
+    image_path = artifacts.get_image_path(artifact_dir)
 
-    logger.info("Generating image")
-    generated_image_url = generate_image(prompt)
+    guessability = None
+    if config.get_bool_for_difficulty("GUESSABILITY_CHECK", difficulty):
+        with run_report.stage("guessability", difficulty):
+            guesses = guess_words(image_path, difficulty)
+        if guesses is not None:
+            guessability = score_guessability(words, guesses)
+            problem = check_guessability(difficulty, guessability)
+            run_report.record_qa(
+                difficulty, "guessability", problem is None, f"{guessability} ({problem or 'fine'})"
+            )
+            if problem is not None:
+                logger.warning(
+                    "The %s challenge may be at the wrong level, %s (guessed %s)",
+                    difficulty,
+                    problem,
+                    ", ".join(guesses),
+                )
 
-    # Download/resize/upload image
-    with NamedTemporaryFile(delete=False) as image_temp_file:
-        logger.info("Downloading temporary file")
-        urlretrieve(generated_image_url, image_temp_file.name)
+    artifact = ChallengeArtifact(
+        words=words,
+        prompt_variant=prompt_variant.name,
+        prompt=generated_prompt,
+        hints=hints,
+        decoys=decoys,
+        difficulty_score=difficulty_score,
+        guessability=guessability,
+        image_sha256=get_sha256(image_path),
+    )
+    artifacts.save(artifact_dir, artifact)
+    return artifact
 
-        logger.info("Processing images and generating jpg/webp files")
-        images_for_web = generate_images_for_web(image_temp_file.name)
 
-        logger.info("Uploading images to CDN")
-        cdn_jpeg_url = cdn.upload_file(
-            images_for_web.jpeg_path,
-            f"{date_to_generate_for}/{images_for_web.jpeg_filename}",
+# Process/upload image
+def create_challenge(
+    artifact: ChallengeArtifact,
+    artifact_dir: str,
+    date_to_generate_for: str,
+    difficulty: str,
+    run_report: RunReport,
+    staging: Staging,
+) -> Challenge:
+    image_path = artifacts.get_image_path(artifact_dir)
+    size = int(config.get("WEB_IMAGE_SIZE", WEB_IMAGE_SIZE))
+    source_path = image_path
+    if config.get_bool("UPSCALE"):
+        logger.info("Upscaling image to %spx", size)
+        source_path = os.path.join(artifact_dir, "upscaled.png")
+        with run_report.stage("upscale", difficulty):
+            upscale_image(image_path, size, source_path)
+
+    logger.info("Processing images and generating jpg/webp files")
+    with run_report.stage("process", difficulty):
+        # The renditions are uploaded straight from memory, they're only written out when they're wanted for a look
+        images_for_web = generate_images_for_web(
+            source_path, artifact_dir if config.get_bool("KEEP_WEB_IMAGES") else None, size
         )
-        cdn_webp_url = cdn.upload_file(
-            images_for_web.webp_path,
-            f"{date_to_generate_for}/{images_for_web.webp_filename}",
+
+    logger.info("Uploading images to CDN")
+    with run_report.stage("upload", difficulty):
+        cdn_jpeg_url = staging.upload_bytes(
+            images_for_web.jpeg.data,
+            keys.get_key("image", date=date_to_generate_for, filename=images_for_web.jpeg.filename),
+            images_for_web.jpeg.content_type,
         )
-        return Challenge(
-            words=words,
-            image_path=image_temp_file.name,
-            image_url_jpg=cdn_jpeg_url,
-            image_url_webp=cdn_webp_url,
-            prompt=prompt,
+        cdn_webp_url = staging.upload_bytes(
+            images_for_web.webp.data,
+            keys.get_key("image", date=date_to_generate_for, filename=images_for_web.webp.filename),
+            images_for_web.webp.content_type,
         )
 
+    narration_url = None
+    if config.get_bool("NARRATION"):
+        logger.info("Generating narration")
+        with run_report.stage("narration", difficulty):
+            narration_url = staging.upload_bytes(
+                generate_speech(artifact.prompt.description),
+                keys.get_key("narration", date=date_to_generate_for, filename=f"{uuid4()}.mp3"),
+                "audio/mpeg",
+            )
 
-@retry(stop=stop_after_attempt(3), wait=wait_fixed(2 * 60))
-def generate_for_date(date_to_generate_for: str):
-    # Get days.json
-    try:
-        days_json = read_public_json(f"days.json?id={str(uuid4())}")
-        days = Days.parse_obj(days_json)
-    except:
-        rollbar.report_exc_info()
-        logger.error("Failed to fetch days.json, starting over with a new one")
-        days = Days(days=[])
+    prompt_i18n = {}
+    locales = config.get_list("PROMPT_LOCALES")
+    if locales:
+        logger.info("Translating prompt into %s", ", ".join(locales))
+        with run_report.stage("translate", difficulty):
+            for locale in locales:
+                prompt_i18n[locale] = translate_prompt(artifact.prompt.description, locale, difficulty)
+    return Challenge(
+        difficulty=difficulty,
+        words=artifact.words,
+        image_path=image_path,
+        image_url_jpg=cdn_jpeg_url,
+        image_url_webp=cdn_webp_url,
+        image_sha256_jpg=images_for_web.jpeg.sha256,
+        image_sha256_webp=images_for_web.webp.sha256,
+        image_bytes_jpg=images_for_web.jpeg.bytes,
+        image_bytes_webp=images_for_web.webp.bytes,
+        prompt=artifact.prompt.description,
+        title=artifact.prompt.title,
+        hint=artifact.prompt.hint,
+        hints=artifact.hints,
+        decoys=artifact.decoys,
+        difficulty_score=artifact.difficulty_score,
+        guessability=artifact.guessability,
+        narration_url=narration_url,
+        prompt_i18n=prompt_i18n,
+    )
+
+
+# Creates the challenge for each of the given difficulties. With PARTIAL_PUBLISH a difficulty that fails is left out
+# and returned as missing, instead of throwing away the ones that were already paid for. Fatal errors would fail every
+# difficulty the same way, so they still abort.
+def create_challenges(
+    words: dict[str, list[Word]],
+    date_to_generate_for: str,
+    style: str,
+    excluded_words: set[str],
+    prompt_variants: dict[str, str],
+    run_report: RunReport,
+    staging: Staging,
+) -> tuple[dict[str, Challenge], list[str]]:
+    completed_challenges = {}
+    missing = []
+    for difficulty, difficulty_words in words.items():
+        artifact_dir = artifacts.get_artifact_dir(run_report.output_dir, difficulty)
+        try:
+            artifact = artifacts.load(artifact_dir, difficulty_words)
+            if artifact is not None:
+                logger.info("Reusing the %s prompt and image from an earlier attempt", difficulty)
+            else:
+                prompt_variant = choose_prompt_variant()
+                logger.info("Using prompt variant %s for %s", prompt_variant.name, difficulty)
+                artifact = generate_artifact(
+                    difficulty_words,
+                    date_to_generate_for,
+                    difficulty,
+                    style,
+                    prompt_variant,
+                    excluded_words,
+                    run_report,
+                    artifact_dir,
+                )
+            prompt_variants[difficulty] = artifact.prompt_variant
+            completed_challenges[difficulty] = create_challenge(
+                artifact, artifact_dir, date_to_generate_for, difficulty, run_report, staging
+            )
+        except Exception as e:
+            if isinstance(e, FatalError) or not config.get_bool("PARTIAL_PUBLISH"):
+                raise
+            rollbar.report_exc_info(extra_data=get_context(e))
+            logger.error("Failed to generate the %s challenge, publishing without it", difficulty)
+            missing.append(difficulty)
+            last_error = e
+        else:
+            run_report.challenges_succeeded += 1
+            progress.update(date_to_generate_for, difficulty, "done")
+    if not completed_challenges:
+        raise last_error
+    return completed_challenges, missing
+
+
+def stage_share_image(
+    date_to_generate_for: str, image_paths: dict[str, str], staging: Staging
+) -> str:
+    with NamedTemporaryFile(suffix=".jpg", delete=False) as share_image_file:
+        font_path = config.get("SHARE_IMAGE_FONT_PATH", SHARE_IMAGE_FONT_PATH)
+        generate_share_image(
+            [image_paths.get(difficulty) for difficulty in DIFFICULTIES],
+            date_to_generate_for,
+            share_image_file.name,
+            font_path if os.path.exists(font_path) else None,
+        )
+        return staging.upload_file(
+            share_image_file.name, keys.get_key("share_image", date=date_to_generate_for)
+        )
+
+
+# Challenges.present() goes easiest first
+def get_challenge_list(challenges: Challenges) -> list[Challenge] | None:
+    if not config.get_bool("CHALLENGE_LIST"):
+        return None
+    return list(challenges.present().values())
 
-    # Get ID for today
-    # TODO: Need to make it "overwrite" it if the date already exists and it was added to days.json
+
+# The title comes from the dreaming challenge, or whichever one there is if that's missing
+def get_title_prompt(challenges: Challenges) -> str:
+    return (challenges.dreaming or next(iter(challenges.present().values()))).prompt
+
+
+# A date that's being generated again keeps the ID it has
+def get_challenge_id(days: Days, date_to_generate_for: str) -> int:
+    for day in days.days:
+        if day.date == date_to_generate_for:
+            return day.id
     challenge_id = -1
     for day in days.days:
         if day.id > challenge_id:
             challenge_id = day.id
-    challenge_id += 1
+    if challenge_id >= DAY_ID_MAX:
+        raise FatalError(f"Out of day IDs, {challenge_id} is the largest the frontend can take")
+    return challenge_id + 1
+
+
+# Everything publish_indexes writes for the day
+def get_index_keys(for_day: Day) -> list[str]:
+    index_keys = [
+        keys.get_key("days"),
+        indexes.get_shard_key(indexes.get_year(for_day.date)),
+        keys.get_key("days_manifest"),
+        archive.get_archive_key(for_day.date[:7]),
+        keys.get_key("sitemap"),
+        keys.get_key("stats"),
+    ]
+    if for_day.date == get_today_str():
+        index_keys += [keys.get_key("today"), signing.get_signature_key(keys.get_key("today"))]
+    return index_keys
+
+
+# Adds or updates the day in days.json and updates everything that's built from it. With staging, they're backed up
+# first so a failure can be rolled back.
+def publish_indexes(days: Days, for_day: Day, run_report: RunReport, staging: Staging | None = None):
+    if staging is not None:
+        staging.protect(get_index_keys(for_day))
+    logger.info("Updating days file")
+    indexes.set_date_entry(days, for_day)
+    upload_json(run_report, days.model_dump_json(), keys.get_key("days"))
+
+    logger.info("Updating days index shard")
+    uploaded_shards = indexes.publish_shards(days, [indexes.get_year(for_day.date)])
+    for key, url in uploaded_shards.items():
+        run_report.record_upload(key, url)
+
+    logger.info("Updating monthly archive")
+    archive_key, archive_url = archive.publish_month_archive(days, for_day)
+    run_report.record_upload(archive_key, archive_url)
 
-    logger.info("ID assigned to date is %s", challenge_id)
+    logger.info("Updating sitemap")
+    sitemap_key = keys.get_key("sitemap")
+    sitemap_url = cdn.upload_bytes(
+        sitemap.build_sitemap(days).encode("utf-8"), sitemap_key, "application/xml"
+    )
+    run_report.record_upload(sitemap_key, sitemap_url)
+
+    logger.info("Updating stats")
+    stats_key, stats_url = stats.publish_stats(days, for_day)
+    run_report.record_upload(stats_key, stats_url)
+
+    # If date to generate for is today, replace today.json with today's data.
+    if for_day.date == get_today_str():
+        logger.info("Updating today's file")
+        upload_day(for_day, keys.get_key("today"), run_report)
+    else:
+        logger.info("Not today, not updating today.json")
+
+
+def fetch_days() -> Days:
+    try:
+        return migrate_days(read_fresh_json(keys.get_key("days")))
+    except FatalError:
+        # A days.json this generator can't read isn't one to start over from
+        raise
+    except:
+        rollbar.report_exc_info()
+        logger.error("Failed to fetch days.json, starting over with a new one")
+        return Days(days=[])
+
+
+# Held from reading days.json until the indexes built from it are published
+publish_lock = threading.Lock()
+
+
+# Fatal errors (a bad API key, missing config) fail the same way every time, so they aren't worth two more attempts
+retry_unless_fatal = retry(
+    stop=stop_after_attempt(3),
+    wait=wait_fixed(2 * 60),
+    retry=retry_if_not_exception_type(FatalError),
+    reraise=True,
+)
+
+
+@retry_unless_fatal
+def generate_for_date(date_to_generate_for: str, run_report: RunReport) -> Day:
+    run_report.attempts += 1
+    run_report.challenges_succeeded = 0
 
     # Generate words for today
     logger.info("Generating words for today")
-    words_for_day = generate_words_for_day(date_to_generate_for)
+    words_for_day = artifacts.load_words(run_report.output_dir)
+    if words_for_day is None:
+        words_for_day = generate_words_for_day(date_to_generate_for)
+        artifacts.save_words(run_report.output_dir, words_for_day)
     logger.info("Words generated")
 
+    # Decoys must never be one of the day's real answers, in any difficulty
+    all_words = {
+        word.word
+        for difficulty in DIFFICULTIES
+        for word in getattr(words_for_day, difficulty)
+    }
+
+    style = get_style_for_day(date_to_generate_for)
+    logger.info("Style for the day is %s", style)
+
     # For each set of words, create prompt and then create/process/upload images
-    # TODO: Better error handling for generating the challenges - I've gotten some 'content' errors, but since this
-    # whole block is retried and sorta idempotent, should be fine?
+    prompt_variants = {}
+    staging = Staging(f"{run_report.run_id}-{run_report.attempts}")
+    try:
+        completed_challenges, missing = create_challenges(
+            {difficulty: getattr(words_for_day, difficulty) for difficulty in DIFFICULTIES},
+            date_to_generate_for,
+            style,
+            all_words,
+            prompt_variants,
+            run_report,
+            staging,
+        )
+        challenges = Challenges(**completed_challenges)
+
+        logger.info("Generating title for the day")
+        with run_report.stage("title"):
+            title = generate_day_title(get_title_prompt(challenges))
+
+        logger.info("Generating share image")
+        with run_report.stage("share_image"):
+            share_image_url = stage_share_image(
+                date_to_generate_for,
+                {difficulty: challenge.image_path for difficulty, challenge in completed_challenges.items()},
+                staging,
+            )
+        # days.json is read and written in one go so days being generated alongside (see backfill) don't drop
+        # each other's entries or end up with the same ID
+        with publish_lock:
+            days = fetch_days()
+            challenge_id = get_challenge_id(days, date_to_generate_for)
+            logger.info("ID assigned to date is %s", challenge_id)
+
+            for_day = Day(
+                date=date_to_generate_for,
+                id=challenge_id,
+                challenges=challenges,
+                challenge_list=get_challenge_list(challenges),
+                title=title,
+                style=style,
+                share_image_url=share_image_url,
+                meta=GenerationMeta(
+                    generated_at=datetime.now(timezone.utc),
+                    generator_version=config.get("GENERATOR_VERSION", GENERATOR_VERSION),
+                    provider=get_provider(),
+                    models={difficulty: get_models(difficulty) for difficulty in DIFFICULTIES},
+                    attempts=run_report.attempts,
+                    templates=TemplateVersions(
+                        prompt=PROMPT_TEMPLATE_VERSION, image=IMAGE_TEMPLATE_VERSION
+                    ),
+                    experiment=config.get("PROMPT_EXPERIMENT_NAME"),
+                    prompt_variants=prompt_variants,
+                ),
+                missing=missing,
+                publish_at=get_publish_at(date_to_generate_for),
+            )
+
+            logger.info("Staging day")
+            upload_day(for_day, keys.get_key("day", date=date_to_generate_for), run_report, staging)
+
+            logger.info("Staging answer vectors")
+            with run_report.stage("vectors"):
+                staging.upload_json(
+                    vectors.build_day_vectors(for_day).model_dump_json(),
+                    vectors.get_vectors_key(date_to_generate_for),
+                )
+
+            publish_staged(staging, days, for_day, run_report)
+        return for_day
+    except Exception as e:
+        discard_staged(staging)
+        if isinstance(e, GeneratorError):
+            e.attempt = run_report.attempts
+        context = get_context(e)
+        rollbar.report_exc_info(extra_data=context)
+        if isinstance(e, FatalError):
+            logger.error("Failed to generate challenges, not retrying", extra=context)
+        else:
+            logger.error("Failed to generate challenges, starting over", extra=context)
+        raise
+
+
+# Words for a difficulty that don't repeat any already used that day
+def pick_words(difficulty: str, used_words: set[str]) -> list[Word]:
+    while True:
+        words = generate_word_list(Difficulty[difficulty.upper()])
+        if len({word.word for word in words}) == len(words) and not any(
+            word.word in used_words for word in words
+        ):
+            return words
+
+
+# Fills in the challenges a partially published day is missing
+@retry_unless_fatal
+def regen_for_date(date_to_generate_for: str, run_report: RunReport) -> Day:
+    run_report.attempts += 1
+    for_day = fetch_day(date_to_generate_for)
+    if not for_day.missing:
+        logger.info("%s isn't missing any challenges, nothing to regenerate", date_to_generate_for)
+        return for_day
+
+    present = for_day.challenges.present()
+    run_report.challenges_succeeded = len(present)
+    used_words = {word.word for challenge in present.values() for word in challenge.words}
+    words = {}
+    for difficulty in for_day.missing:
+        words[difficulty] = pick_words(difficulty, used_words)
+        used_words |= {word.word for word in words[difficulty]}
+
+    logger.info("Regenerating %s for %s", ", ".join(for_day.missing), date_to_generate_for)
+    style = for_day.style or get_style_for_day(date_to_generate_for)
+    prompt_variants = dict(for_day.meta.prompt_variants) if for_day.meta else {}
+    staging = Staging(f"{run_report.run_id}-{run_report.attempts}")
     try:
-        easy_challenge = create_challenge(words_for_day.easy, date_to_generate_for)
-        medium_challenge = create_challenge(words_for_day.medium, date_to_generate_for)
-        hard_challenge = create_challenge(words_for_day.hard, date_to_generate_for)
-        dreaming_challenge = create_challenge(
-            words_for_day.dreaming, date_to_generate_for
+        completed_challenges, missing = create_challenges(
+            words, date_to_generate_for, style, used_words, prompt_variants, run_report, staging
         )
-        challenges = Challenges(
-            easy=easy_challenge,
-            medium=medium_challenge,
-            hard=hard_challenge,
-            dreaming=dreaming_challenge,
+        challenges = for_day.challenges.model_copy(update=completed_challenges)
+
+        title = for_day.title
+        if "dreaming" in completed_challenges:
+            logger.info("Generating title for the day")
+            with run_report.stage("title"):
+                title = generate_day_title(challenges.dreaming.prompt)
+
+        # The images that weren't regenerated only exist on the CDN by now
+        logger.info("Generating share image")
+        with run_report.stage("share_image"):
+            image_paths = {
+                difficulty: challenge.image_path
+                for difficulty, challenge in completed_challenges.items()
+            }
+            for difficulty, challenge in present.items():
+                with NamedTemporaryFile(suffix=".jpg", delete=False) as image_file:
+                    urlretrieve(challenge.image_url_jpg, image_file.name)
+                    image_paths[difficulty] = image_file.name
+            share_image_url = stage_share_image(date_to_generate_for, image_paths, staging)
+
+        meta = for_day.meta
+        if meta is not None:
+            meta = meta.model_copy(update={"prompt_variants": prompt_variants})
+        for_day = for_day.model_copy(
+            update={
+                "challenges": challenges,
+                "challenge_list": get_challenge_list(challenges),
+                "title": title,
+                "share_image_url": share_image_url,
+                "meta": meta,
+                "missing": missing,
+            }
         )
-        for_day = Day(date=date_to_generate_for, id=challenge_id, challenges=challenges)
-
-        # Upload day to CDN
-        logger.info("Uploading day to CDN")
-        with NamedTemporaryFile(delete=False) as today_file:
-            today_file.write(for_day.model_dump_json().encode("utf-8"))
-            today_file.close()
-            cdn.upload_file(today_file.name, f"days/{date_to_generate_for}.json")
-
-            # Update days.json with today's data
-            logger.info("Updating days file")
-            days.days.append(DateEntry(id=for_day.id, date=for_day.date))
-            with NamedTemporaryFile(delete=False) as new_days_file:
-                new_days_file.write(days.model_dump_json().encode("utf-8"))
-                new_days_file.close()
-                cdn.upload_file(new_days_file.name, f"days.json")
-
-            # If date to generate for is today, replace today.json with today's data.
-            if date_to_generate_for == get_today_str():
-                logger.info("Updating today's file")
-                cdn.upload_file(today_file.name, "today.json")
-            else:
-                logger.info("Not today, not updating today.json")
-    except:
-        rollbar.report_exc_info()
-        logger.error("Failed to generate challenges, starting over")
+
+        logger.info("Staging day")
+        upload_day(for_day, keys.get_key("day", date=date_to_generate_for), run_report, staging)
+
+        logger.info("Staging answer vectors")
+        with run_report.stage("vectors"):
+            staging.upload_json(
+                vectors.build_day_vectors(for_day).model_dump_json(),
+                vectors.get_vectors_key(date_to_generate_for),
+            )
+
+        # The day's entry in days.json changes along with its file
+        with publish_lock:
+            publish_staged(staging, migrate_days(read_fresh_json(keys.get_key("days"))), for_day, run_report)
+        return for_day
+    except Exception as e:
+        discard_staged(staging)
+        if isinstance(e, GeneratorError):
+            e.attempt = run_report.attempts
+        context = get_context(e)
+        rollbar.report_exc_info(extra_data=context)
+        logger.error("Failed to regenerate challenges", extra=context)
+        raise
+
+
+# Keeps the scheduler from overwriting a day that's already out, which may well have been fixed by hand
+def is_published(date_to_generate_for: str) -> bool:
+    if not cdn.exists(keys.get_key("day", date=date_to_generate_for)):
+        return False
+    logger.info(
+        "%s has already been published, pass --force to generate it again", date_to_generate_for
+    )
+    return True
+
+
+# A day generated ahead of time that has reached its publish_at, which what's built from days.json doesn't show yet
+def is_due_for_release(date_to_release: str) -> bool:
+    for_day = fetch_day(date_to_release)
+    return for_day.publish_at is not None and indexes.is_live(for_day.publish_at)
+
+
+# Publishes the indexes again once a day generated ahead of time is live, so it makes it into the archive, sitemap,
+# stats and today.json
+@retry_unless_fatal
+def release_date(date_to_release: str, run_report: RunReport) -> Day:
+    run_report.attempts += 1
+    for_day = fetch_day(date_to_release)
+    logger.info("Releasing %s, it went live at %s", date_to_release, for_day.publish_at.isoformat())
+    with publish_lock:
+        publish_indexes(migrate_days(read_fresh_json(keys.get_key("days"))), for_day, run_report)
+    return for_day
+
+
+# Copies a day that was published to PROMOTE_FROM_BUCKET (i.e. with the staging profile) into this profile's bucket
+# once it's been looked over. The day gets an ID from this bucket's days.json, not the one it had in staging.
+@retry_unless_fatal
+def promote_date(date_to_promote: str, run_report: RunReport) -> Day:
+    return copy_day(date_to_promote, run_report, check_embargo=True)
+
+
+# A SITE_MIRROR site copies the other site's day as soon as it's generated. One generated ahead of time keeps its
+# publish_at, so it's held back here the same way it is there.
+@retry_unless_fatal
+def mirror_date(date_to_mirror: str, run_report: RunReport) -> Day:
+    return copy_day(date_to_mirror, run_report, check_embargo=False)
+
+
+def copy_day(date_to_promote: str, run_report: RunReport, check_embargo: bool) -> Day:
+    run_report.attempts += 1
+    source_bucket = config.get_required("PROMOTE_FROM_BUCKET")
+    source_base_url = config.get_required("PROMOTE_FROM_BASE_URL").rstrip("/")
+    source_key_prefix = config.get("PROMOTE_FROM_KEY_PREFIX", "")
+    source_url_prefix = f"{source_base_url}/{source_key_prefix}"
+    source_day = migrate_day(
+        cdn.read_json(keys.get_key("day", date=date_to_promote), source_bucket, source_key_prefix)
+    )
+    if check_embargo and not indexes.is_live(source_day.publish_at):
+        raise FatalError(f"{date_to_promote} is embargoed until {source_day.publish_at.isoformat()}")
+
+    source_keys = [vectors.get_vectors_key(date_to_promote)]
+    for challenge in source_day.challenges.present().values():
+        source_keys += [challenge.image_url_jpg, challenge.image_url_webp]
+        if challenge.narration_url:
+            source_keys.append(challenge.narration_url)
+    if source_day.share_image_url:
+        source_keys.append(source_day.share_image_url)
+    with run_report.stage("promote"):
+        for key in source_keys:
+            key = key.removeprefix(source_url_prefix)
+            logger.info("Promoting %s", key)
+            run_report.record_upload(
+                key, cdn.copy(key, key, source_bucket=source_bucket, source_key_prefix=source_key_prefix)
+            )
+
+    # Every URL in the day points at the staging bucket, and the keys are the same on both sides
+    for_day = migrate_day(json.loads(source_day.model_dump_json().replace(source_url_prefix, cdn.get_url(""))))
+    with publish_lock:
+        days = migrate_days(read_fresh_json(keys.get_key("days")))
+        for_day.id = get_challenge_id(days, date_to_promote)
+        logger.info("ID assigned to date is %s", for_day.id)
+        upload_day(for_day, keys.get_key("day", date=date_to_promote), run_report)
+        publish_indexes(days, for_day, run_report)
+    return for_day
 
 
 def main(args: typing.Dict[str, str]):
-    date_to_generate_for = args.get("date", get_today_str())
-    # TODO: Validate date_to_generate_for is a date
-    logger.info("Generating images for date: %s", date_to_generate_for)
-    generate_for_date(date_to_generate_for)
+    config.load(args.get("config"), args.get("profile"))
+    if args.get("output_dir"):
+        config.set_override("OUTPUT_DIR", args["output_dir"])
+    if args.get("data_dir"):
+        config.set_override("DATA_DIR", args["data_dir"])
+    set_log_level(args.get("verbose") or 0, args.get("quiet"))
+    # The same for every site and config
+    if args.get("command") == "schema":
+        schemas.export_schemas(args.get("output") or schemas.SCHEMA_DIR)
+        return
+    sites = config.get_list("SITES")
+    # An explicitly chosen profile is one site, or an environment of it
+    if args.get("profile") or os.environ.get(config.PROFILE_ENV) or not sites:
+        run_command(args)
+    else:
+        run_sites(args, sites)
+
+
+# Runs the command for each site in SITES, a profile each with its own bucket, base URL and so on. A site failing
+# doesn't stop the ones after it.
+def run_sites(args: typing.Dict[str, str], sites: list[str]):
+    exit_codes = {}
+    for site in sites:
+        logger.info("Running %s for site %s", args.get("command") or "run", site)
+        config.load(args.get("config"), site)
+        try:
+            run_command(args)
+        except SystemExit as e:
+            if e.code:
+                exit_codes[site] = e.code
+        except Exception as e:
+            logger.exception("Failed for site %s", site)
+            exit_codes[site] = get_exit_code(e)
+    if exit_codes:
+        logger.error("Failed for sites: %s", ", ".join(exit_codes))
+        codes = set(exit_codes.values())
+        sys.exit(codes.pop() if len(codes) == 1 else EXIT_FAILED)
+
+
+def run_command(args: typing.Dict[str, str]):
+    command = args.get("command") or "run"
+    # Before setup, which would fail on the first missing key instead of reporting them all
+    if command == "doctor":
+        if not doctor.run_checks():
+            sys.exit(EXIT_FAILED)
+        return
+    if command == "models":
+        catalog.print_models(args.get("all"))
+        return
+    # Before setup, so bad arguments are reported like argparse's own rather than as a failed run
+    if args.get("date"):
+        check_date(args["date"], command == "run", args.get("allow_future"))
+    if command == "backfill":
+        dates = get_dates(args["from"], args["to"], args.get("allow_future"))
+    setup()
+    if command == "migrate":
+        logger.info("Migrating published days to schema version %s", SCHEMA_VERSION)
+        migrate_published()
+    elif command == "history":
+        if args.get("history_command") == "show":
+            if not history.print_run(args["run_id"]):
+                sys.exit(1)
+        else:
+            history.print_runs(args.get("limit") or history.HISTORY_LIMIT, args.get("failed"))
+    elif command == "clean":
+        removed = retention.clean_output()
+        logger.info("Removed %s old run output directories", len(removed))
+    elif command == "gc":
+        garbage.collect_garbage(args.get("dry_run"))
+    elif command == "reindex":
+        logger.info("Rebuilding days index shards from days.json")
+        indexes.publish_shards(migrate_days(read_public_json(f'{keys.get_key("days")}?id={str(uuid4())}')))
+    elif command == "regen":
+        run(args, regen_for_date)
+    elif command == "promote":
+        run(args, promote_date)
+    elif command == "backfill":
+        backfill(args, dates)
+    elif not args.get("force") and is_published(args.get("date") or get_today_str()):
+        if is_due_for_release(args.get("date") or get_today_str()):
+            run(args, release_date)
+        else:
+            # Still a successful run as far as the scheduler's health checks go
+            notifications.notify_skipped(args.get("date") or get_today_str())
+            check_in()
+    elif config.get_bool("SITE_MIRROR"):
+        # Publishes the day another site generated rather than one of its own
+        run(args, mirror_date)
+    else:
+        run(args, generate_for_date)
+
+
+# The run store is a record of the run, not part of it, so a problem with it is only logged
+def save_to_run_store(run_report: RunReport):
+    try:
+        runstore.save(run_report)
+    except Exception:
+        logger.exception("Failed to save run %s to the run store", run_report.run_id)
+
+
+# Makes word and variant picks repeatable, which replaying recorded provider fixtures relies on
+def seed_random():
+    if config.get("RANDOM_SEED") is not None:
+        random.seed(config.get("RANDOM_SEED"))
+
+
+def run(args: typing.Dict[str, str], generate: typing.Callable[[str, RunReport], Day]):
+    seed_random()
+    date_to_generate_for = args.get("date") or get_today_str()
+    retention.clean_output()
+    run_date(date_to_generate_for, args.get("command") or "run", generate)
     check_in()
 
 
+# One date's run, with its own output directory, report and notifications
+def run_date(date_to_generate_for: str, command: str, generate: typing.Callable[[str, RunReport], Day]) -> Day:
+    logger.info("Generating images for date: %s", date_to_generate_for)
+    run_id = uuid4().hex[:8]
+    run_output_dir = os.path.join(retention.get_output_dir(), f"{date_to_generate_for}_{run_id}")
+    os.makedirs(run_output_dir, exist_ok=True)
+    if config.get_bool("PROVIDER_DEBUG_LOG"):
+        ai.enable_debug_log(os.path.join(run_output_dir, "provider_debug.jsonl"))
+    run_report = start_report(date_to_generate_for, run_id, command)
+    run_report.output_dir = run_output_dir
+    save_to_run_store(run_report)
+    ai.reset_token_usage()
+    try:
+        for_day = generate(date_to_generate_for, run_report)
+        run_report.finish()
+    except Exception as e:
+        run_report.finish(e)
+        notifications.notify_failure(date_to_generate_for, e, run_report.challenges_succeeded)
+        raise
+    finally:
+        progress.clear(date_to_generate_for)
+        run_report.token_usage = [
+            TokenUsage(model=model, **usage) for model, usage in sorted(ai.get_token_usage().items())
+        ]
+        run_report.write(run_output_dir)
+        save_to_run_store(run_report)
+    notifications.notify_success(for_day)
+    return for_day
+
+
+def get_dates(from_date: str, to_date: str, allow_future: bool = False) -> list[str]:
+    start = datetime.strptime(check_date(from_date), DATE_FORMAT).date()
+    end = datetime.strptime(check_date(to_date, True, allow_future), DATE_FORMAT).date()
+    if start > end:
+        raise ArgumentError(f"--from {from_date} is after --to {to_date}")
+    return [(start + timedelta(days=offset)).strftime(DATE_FORMAT) for offset in range((end - start).days + 1)]
+
+
+# Generates every date in a range, a few at a time. Each date is its own run (output directory, report, retries and
+# notifications); PROVIDER_CONCURRENCY still caps the provider requests across all of them.
+def backfill(args: typing.Dict[str, str], dates: list[str]):
+    seed_random()
+    concurrency = args.get("concurrency") or int(config.get("BACKFILL_CONCURRENCY", BACKFILL_CONCURRENCY))
+    retention.clean_output()
+
+    results = {}
+    exit_codes = set()
+    pending = []
+    for date_to_generate_for in dates:
+        if not args.get("force") and is_published(date_to_generate_for):
+            results[date_to_generate_for] = "skipped", "already published"
+        else:
+            pending.append(date_to_generate_for)
+
+    logger.info("Backfilling %s dates, %s at a time", len(pending), concurrency)
+    with ThreadPoolExecutor(max_workers=concurrency) as executor:
+        futures = {
+            executor.submit(run_date, date_to_generate_for, "backfill", generate_for_date): date_to_generate_for
+            for date_to_generate_for in pending
+        }
+        for future in as_completed(futures):
+            date_to_generate_for = futures[future]
+            try:
+                for_day = future.result()
+            except Exception as e:
+                logger.error("Failed to backfill %s", date_to_generate_for)
+                results[date_to_generate_for] = "failed", str(e)
+                exit_codes.add(get_exit_code(e))
+            else:
+                missing = f"missing {', '.join(for_day.missing)}" if for_day.missing else ""
+                results[date_to_generate_for] = "published", missing
+
+    print(f"{'DATE':<12} {'RESULT':<10} DETAIL")
+    for date_to_generate_for in dates:
+        result, detail = results[date_to_generate_for]
+        print(f"{date_to_generate_for:<12} {result:<10} {detail}")
+    failed = [date_to_generate_for for date_to_generate_for, (result, _) in results.items() if result == "failed"]
+    print(f"{len(dates) - len(failed)} of {len(dates)} dates published or skipped, {len(failed)} failed")
+    if failed:
+        # Only the dates failing the same way says more than that something failed
+        sys.exit(exit_codes.pop() if len(exit_codes) == 1 else EXIT_FAILED)
+
+
 if __name__ == "__main__":
-    main({})
+    parser = argparse.ArgumentParser(description="Generates a day for iamdreamingof.com")
+    parser.add_argument("--config", help=f"Path to a TOML config file, defaults to ${config.CONFIG_PATH_ENV}")
+    parser.add_argument(
+        "--profile", help=f"Config file profile to use, defaults to ${config.PROFILE_ENV} or each one in SITES"
+    )
+    parser.add_argument(
+        "--output-dir", help=f"Where run output goes, defaults to $OUTPUT_DIR or {retention.OUTPUT_DIR}"
+    )
+    parser.add_argument(
+        "--data-dir", help="Where the word and style lists are read from, defaults to $DATA_DIR or this directory"
+    )
+    verbosity = parser.add_mutually_exclusive_group()
+    verbosity.add_argument(
+        "-v", "--verbose", action="count", help="Log debug messages, -vv includes the libraries' debug messages too"
+    )
+    verbosity.add_argument("-q", "--quiet", action="store_true", help="Only log warnings and errors")
+    commands = parser.add_subparsers(dest="command", help="Defaults to run")
+    run_parser = commands.add_parser("run", help="Generate and publish a day")
+    run_parser.add_argument("--date", type=parse_date, help="Date to generate for, defaults to today")
+    run_parser.add_argument(
+        "--force", action="store_true", help="Generate the day again even if it's already published"
+    )
+    run_parser.add_argument(
+        "--allow-future",
+        action="store_true",
+        help=f"Allow a date more than $MAX_DAYS_AHEAD or {MAX_DAYS_AHEAD} days ahead",
+    )
+    regen_parser = commands.add_parser(
+        "regen", help="Fill in the challenges a partially published day is missing"
+    )
+    regen_parser.add_argument("--date", type=parse_date, help="Date to fill in, defaults to today")
+    promote_parser = commands.add_parser(
+        "promote", help="Copy a day from PROMOTE_FROM_BUCKET into the configured bucket and publish it"
+    )
+    promote_parser.add_argument("--date", type=parse_date, help="Date to promote, defaults to today")
+    backfill_parser = commands.add_parser("backfill", help="Generate and publish every date in a range")
+    backfill_parser.add_argument("--from", required=True, type=parse_date, help="First date to generate for")
+    backfill_parser.add_argument("--to", required=True, type=parse_date, help="Last date to generate for")
+    backfill_parser.add_argument(
+        "--concurrency",
+        type=int,
+        help=f"How many dates to generate at once, defaults to $BACKFILL_CONCURRENCY or {BACKFILL_CONCURRENCY}",
+    )
+    backfill_parser.add_argument(
+        "--force", action="store_true", help="Generate dates again even if they're already published"
+    )
+    backfill_parser.add_argument(
+        "--allow-future",
+        action="store_true",
+        help=f"Allow dates more than $MAX_DAYS_AHEAD or {MAX_DAYS_AHEAD} days ahead",
+    )
+    commands.add_parser("migrate", help="Rewrite every published day in the current schema")
+    history_parser = commands.add_parser("history", help="Show past runs from the run store")
+    history_commands = history_parser.add_subparsers(dest="history_command", help="Defaults to list")
+    history_list_parser = history_commands.add_parser("list", help="List recent runs")
+    history_list_parser.add_argument("--limit", type=int, help="How many runs to list, defaults to 20")
+    history_list_parser.add_argument("--failed", action="store_true", help="Only list failed runs")
+    history_show_parser = history_commands.add_parser("show", help="Show one run's stages and uploads")
+    history_show_parser.add_argument("run_id")
+    commands.add_parser(
+        "clean", help="Remove old run output directories per OUTPUT_RETENTION_COUNT/DAYS"
+    )
+    gc_parser = commands.add_parser(
+        "gc", help="Delete images that no published day points at"
+    )
+    gc_parser.add_argument(
+        "--dry-run", action="store_true", help="Only list the images that would be deleted"
+    )
+    commands.add_parser("reindex", help="Rebuild the yearly days index shards from days.json")
+    models_parser = commands.add_parser("models", help="Show the models the configured provider has")
+    models_commands = models_parser.add_subparsers(dest="models_command", help="Defaults to list")
+    models_list_parser = models_commands.add_parser(
+        "list", help="List the chat, image, moderation, embedding and speech models, configured ones marked with *"
+    )
+    models_list_parser.add_argument("--all", action="store_true", help="Include models for anything else too")
+    schema_parser = commands.add_parser("schema", help="Work with the JSON Schema of the published files")
+    schema_commands = schema_parser.add_subparsers(dest="schema_command", help="Defaults to export")
+    schema_export_parser = schema_commands.add_parser(
+        "export", help="Write a JSON Schema for each published file, for the frontend to validate and generate types"
+    )
+    schema_export_parser.add_argument(
+        "--output", help=f"Directory to write the .schema.json files to, defaults to {schemas.SCHEMA_DIR}"
+    )
+    commands.add_parser(
+        "doctor", help="Check the config, API key, models and CDN credentials work, without generating anything"
+    )
+    try:
+        main(vars(parser.parse_args()))
+    except ArgumentError as e:
+        parser.error(str(e))
+    except Exception as e:
+        logger.exception("Failed")
+        sys.exit(get_exit_code(e))
@@ -0,0 +1,130 @@
+import logging
+import typing
+from uuid import uuid4
+
+from pydantic import ValidationError
+
+import cdn
+import errors
+import indexes
+import keys
+import signing
+from models import SCHEMA_VERSION, Day, Days, PublishedModel
+
+logger = logging.getLogger(__name__)
+
+Migration = typing.Callable[[dict], dict]
+
+
+# Payloads published before schema_version existed are version 0. All the fields added since are optional, so
+# stamping the version is all that's needed.
+def day_0_to_1(payload: dict) -> dict:
+    return {**payload, "schema_version": 1}
+
+
+def days_0_to_1(payload: dict) -> dict:
+    return {**payload, "schema_version": 1}
+
+
+# Version 2 added a day's meta, publish_at and challenge_list, each challenge's difficulty, and each days.json entry's
+# sha256, updated_at and publish_at. Only the difficulty is always written now, and it's the challenge's key.
+def day_1_to_2(payload: dict) -> dict:
+    challenges = {
+        difficulty: {"difficulty": difficulty, **challenge} if challenge is not None else None
+        for difficulty, challenge in payload.get("challenges", {}).items()
+    }
+    return {**payload, "challenges": challenges, "schema_version": 2}
+
+
+def days_1_to_2(payload: dict) -> dict:
+    return {**payload, "schema_version": 2}
+
+
+# Keyed by the version a migration upgrades from
+DAY_MIGRATIONS: dict[int, Migration] = {0: day_0_to_1, 1: day_1_to_2}
+DAYS_MIGRATIONS: dict[int, Migration] = {0: days_0_to_1, 1: days_1_to_2}
+
+
+def upgrade(payload: dict, migrations: dict[int, Migration]) -> dict:
+    version = payload.get("schema_version", 0)
+    if version > SCHEMA_VERSION:
+        raise errors.FatalError(
+            f"Published JSON is schema version {version} but this generator only knows up to {SCHEMA_VERSION}, "
+            "it needs updating before it can read it"
+        )
+    while version < SCHEMA_VERSION:
+        payload = migrations[version](payload)
+        version = payload["schema_version"]
+    return payload
+
+
+# A payload that doesn't fit the models after upgrading is reported as a whole rather than dropping what doesn't fit
+def validate(model: type[PublishedModel], payload: dict) -> PublishedModel:
+    try:
+        return model.model_validate(payload)
+    except ValidationError as e:
+        raise errors.FatalError(
+            f"Published {model.__name__} doesn't match schema version {SCHEMA_VERSION}: {e}"
+        ) from e
+
+
+def migrate_day(payload: dict) -> Day:
+    return validate(Day, upgrade(payload, DAY_MIGRATIONS))
+
+
+def migrate_days(payload: dict) -> Days:
+    return validate(Days, upgrade(payload, DAYS_MIGRATIONS))
+
+
+def read_fresh_json(key: str) -> dict:
+    return cdn.read_public_json(f"{key}?id={str(uuid4())}")
+
+
+def fetch_day(date: str) -> Day:
+    return migrate_day(read_fresh_json(keys.get_key("day", date=date)))
+
+
+# Signed again, as the old signature won't match the rewritten file
+def upload_day(for_day: Day, key: str):
+    for file_key, data in signing.get_signed_files(key, for_day.model_dump_json()).items():
+        cdn.upload_json(data, file_key)
+
+
+# days.json decides a day's ID, a day file that says otherwise is rewritten with it
+def reconcile_id(for_day: Day, key: str, days: Days):
+    for entry in days.days:
+        if entry.date == for_day.date and entry.id != for_day.id:
+            logger.warning("%s has ID %s but days.json gives it %s, rewriting it", key, for_day.id, entry.id)
+            for_day.id = entry.id
+
+
+# IDs are validated as they're read, but whether they agree between days.json and the days only shows across files
+def check_ids(days: Days):
+    seen: dict[int, str] = {}
+    for entry in days.days:
+        if entry.id in seen:
+            raise errors.FatalError(f"{seen[entry.id]} and {entry.date} both have ID {entry.id} in days.json")
+        seen[entry.id] = entry.date
+
+
+# Rewrites everything that's been published in the current schema, with the IDs from days.json
+def migrate_published():
+    days = migrate_days(read_fresh_json(keys.get_key("days")))
+    check_ids(days)
+    for entry in days.days:
+        key = keys.get_key("day", date=entry.date)
+        logger.info("Migrating %s", key)
+        for_day = migrate_day(read_fresh_json(key))
+        reconcile_id(for_day, key, days)
+        upload_day(for_day, key)
+        indexes.set_date_entry(days, for_day)
+
+    logger.info("Migrating today.json")
+    today_key = keys.get_key("today")
+    today = migrate_day(read_fresh_json(today_key))
+    reconcile_id(today, today_key, days)
+    upload_day(today, today_key)
+
+    logger.info("Migrating days.json")
+    cdn.upload_json(days.model_dump_json(), keys.get_key("days"))
+    indexes.publish_shards(days)
@@ -1,6 +1,21 @@
+from datetime import datetime
 from enum import Enum
+from typing import Annotated
 
-from pydantic import BaseModel
+from pydantic import BaseModel, ConfigDict, Field
+
+# Bump when the shape of the published JSON changes, and add a migration for it in migrations.py
+SCHEMA_VERSION = 2
+
+# Days are numbered from 0, and the frontend keeps the ID in an unsigned 32 bit integer
+DAY_ID_MAX = 2**32 - 1
+DayId = Annotated[int, Field(ge=0, le=DAY_ID_MAX)]
+
+
+# Everything that's published. A field this generator doesn't know is an error rather than silently dropped, so a
+# day written by a newer generator can't lose fields by being read and rewritten by an older one.
+class PublishedModel(BaseModel):
+    model_config = ConfigDict(extra="forbid")
 
 
 class Difficulty(Enum):
@@ -10,24 +25,58 @@ class Difficulty(Enum):
     DREAMING = 4
 
 
-class Word(BaseModel):
+class Word(PublishedModel):
     word: str
     type: str
 
 
-class Challenge(BaseModel):
+# Progressively easier hints for a single word: vague, medium, then almost a giveaway
+class WordHints(PublishedModel):
+    word: str
+    hints: list[str]
+
+
+class Challenge(PublishedModel):
+    # easy, medium, hard or dreaming; absent from days published before it was added
+    difficulty: str | None = None
     words: list[Word]
     image_path: str
     image_url_jpg: str
     image_url_webp: str
+    # For checking the published images are intact, and for cache busting
+    image_sha256_jpg: str | None = None
+    image_sha256_webp: str | None = None
+    image_bytes_jpg: int | None = None
+    image_bytes_webp: int | None = None
     prompt: str
-
-
-class Challenges(BaseModel):
-    easy: Challenge
-    medium: Challenge
-    hard: Challenge
-    dreaming: Challenge
+    title: str | None = None
+    hint: str | None = None
+    hints: list[WordHints] = []
+    # Plausible wrong answers for the multiple choice mode
+    decoys: list[Word] = []
+    # Estimated guessability of the words, see calibration.py
+    difficulty_score: float | None = None
+    # Share of the words a vision model guessed from the image, with GUESSABILITY_CHECK on
+    guessability: float | None = None
+    # MP3 reading of the prompt for the reveal, with NARRATION on
+    narration_url: str | None = None
+    # The prompt translated into each of PROMPT_LOCALES, keyed by locale
+    prompt_i18n: dict[str, str] = {}
+
+
+# A difficulty is only ever absent from a partially published day, see Day.missing
+class Challenges(PublishedModel):
+    easy: Challenge | None = None
+    medium: Challenge | None = None
+    hard: Challenge | None = None
+    dreaming: Challenge | None = None
+
+    def present(self) -> dict[str, Challenge]:
+        return {
+            difficulty: challenge
+            for difficulty, challenge in self
+            if challenge is not None
+        }
 
 
 class WordsForDay(BaseModel):
@@ -38,16 +87,119 @@ class WordsForDay(BaseModel):
     dreaming: list[Word]
 
 
-class Day(BaseModel):
+class TemplateVersions(PublishedModel):
+    prompt: int
+    image: int
+
+
+class GenerationMeta(PublishedModel):
+    generated_at: datetime | None = None
+    generator_version: str | None = None
+    provider: str | None = None
+    # Difficulty to capability (chat/image/moderation) to model
+    models: dict[str, dict[str, str]] = {}
+    attempts: int | None = None
+    templates: TemplateVersions
+    experiment: str | None = None
+    # Difficulty to the prompt variant used for it
+    prompt_variants: dict[str, str] = {}
+
+
+class Day(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
     date: str
-    id: int
+    id: DayId
     challenges: Challenges
+    title: str | None = None
+    style: str | None = None
+    share_image_url: str | None = None
+    meta: GenerationMeta | None = None
+    # Difficulties that failed and were left out under PARTIAL_PUBLISH, until `regen` fills them in
+    missing: list[str] = []
+    # The present challenges again, easiest first, with CHALLENGE_LIST on. For consumers that would rather not know the
+    # names of the difficulties, so another one can be added without changing the shape of the day.
+    challenge_list: list[Challenge] | None = None
+    # Start of the day in SITE_TIMEZONE for a day generated ahead of time, which it shouldn't be shown or promoted before
+    publish_at: datetime | None = None
+
+
+# Published next to a day JSON as days/<date>.json.sig, over the exact bytes of that file
+class DaySignature(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    algorithm: str = "ed25519"
+    public_key: str
+    signature: str
+
+
+class DateEntry(PublishedModel):
+    date: str
+    id: DayId
+    # Of the published days/<date>.json, so a stale or corrupted copy can be spotted without parsing it
+    sha256: str | None = None
+    updated_at: datetime | None = None
+    # The day's publish_at, it's listed ahead of time to hold on to its ID but shouldn't be shown before then
+    publish_at: datetime | None = None
+
+
+class Days(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    days: list[DateEntry]
 
 
-class DateEntry(BaseModel):
+class ArchiveEntry(PublishedModel):
     date: str
-    id: int
+    id: DayId
+    title: str | None = None
+    thumbnail_url: str
 
 
-class Days(BaseModel):
-    days: list[DateEntry]
+# Everything the archive page needs for one month, published as archive/<yyyy-mm>.json
+class MonthArchive(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    month: str
+    days: list[ArchiveEntry]
+
+
+class Stats(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    generated_at: datetime
+    days_published: int
+    challenges_published: int
+    # Word type (object/gerund/concept) to word to times used
+    word_usage: dict[str, dict[str, int]]
+    # Capability (chat/image/moderation) to model to challenges generated with it, for days that recorded it
+    models: dict[str, dict[str, int]]
+    providers: dict[str, int]
+    # The days counted, so the next run only has to add the ones since
+    dates: list[str] = []
+
+
+class WordVector(PublishedModel):
+    word: str
+    vector: list[int]
+
+
+# Published as days/<date>.vectors.json so the game can score close guesses without a backend. Vectors are unit length
+# embeddings quantized to int8, multiply by scale to get the floats back.
+class DayVectors(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    date: str
+    model: str
+    dimensions: int
+    scale: float
+    # Difficulty to the vectors of its words
+    challenges: dict[str, list[WordVector]]
+
+
+class ShardEntry(PublishedModel):
+    year: int
+    key: str
+    count: int
+    first_date: str
+    last_date: str
+
+
+# Points at the per-year days-<year>.json shards of the index
+class DaysManifest(PublishedModel):
+    schema_version: int = SCHEMA_VERSION
+    shards: list[ShardEntry]
@@ -0,0 +1,168 @@
+import logging
+import os
+import smtplib
+import typing
+from email.message import EmailMessage
+
+import requests
+
+import config
+import errors
+from models import Day
+
+logger = logging.getLogger(__name__)
+
+# SMTP_TLS to the port it's usually served on: STARTTLS on the submission port, implicit TLS, or plain SMTP for a relay
+# on a trusted network
+SMTP_PORTS = {"starttls": 587, "ssl": 465, "none": 25}
+
+
+class Notifier:
+    def notify_success(self, day: Day):
+        raise NotImplementedError
+
+    def notify_failure(self, date: str, error: Exception, challenges_succeeded: int):
+        raise NotImplementedError
+
+    # The run had nothing to do as the day was already published. Only worth telling a deadman switch about.
+    def notify_skipped(self, date: str):
+        pass
+
+
+# Posts to a Slack or Discord incoming webhook. They take the same idea but a different payload key.
+class WebhookNotifier(Notifier):
+    def __init__(self, url: str):
+        self.url = url
+
+    def post(self, message: str):
+        key = "content" if "discord" in self.url else "text"
+        response = requests.post(self.url, json={key: message}, timeout=10)
+        response.raise_for_status()
+
+    def notify_success(self, day: Day):
+        challenge = day.challenges.dreaming or next(iter(day.challenges.present().values()))
+        message = f"Published {day.date} (#{day.id}): {challenge.image_url_jpg}"
+        if day.missing:
+            message += f" (missing {', '.join(day.missing)}, run regen to fill them in)"
+        self.post(message)
+
+    def notify_failure(self, date: str, error: Exception, challenges_succeeded: int):
+        message = (
+            f"Failed to generate {date} ({challenges_succeeded}/4 challenges succeeded): "
+            f"{type(error).__name__}: {error}"
+        )
+        context = errors.describe_context(errors.get_context(error))
+        if context:
+            message += f" [{context}]"
+        self.post(message)
+
+
+# Failure-only alerts for operators who aren't on Slack/Discord
+class EmailNotifier(Notifier):
+    def __init__(self, host: str, port: int, tls: str, sender: str, recipients: list[str]):
+        self.host = host
+        self.port = port
+        self.tls = tls
+        self.sender = sender
+        self.recipients = recipients
+
+    def notify_success(self, day: Day):
+        pass
+
+    def notify_failure(self, date: str, error: Exception, challenges_succeeded: int):
+        message = EmailMessage()
+        message["Subject"] = f"iamdreamingof generator failed for {date}"
+        message["From"] = self.sender
+        message["To"] = ", ".join(self.recipients)
+        log_file = config.get("LOG_FILE")
+        log_link = f"file://{os.path.abspath(log_file)}" if log_file else "LOG_FILE is not set"
+        message.set_content(
+            f"Generation for {date} failed.\n\n"
+            f"Error: {type(error).__name__}: {error}\n"
+            f"Context: {errors.describe_context(errors.get_context(error)) or '-'}\n"
+            f"Challenges succeeded: {challenges_succeeded}/4\n"
+            f"Log: {log_link}\n"
+        )
+        smtp_class = smtplib.SMTP_SSL if self.tls == "ssl" else smtplib.SMTP
+        with smtp_class(self.host, self.port, timeout=30) as smtp:
+            if self.tls == "starttls":
+                smtp.starttls()
+            username = config.get("SMTP_USERNAME")
+            if username:
+                smtp.login(username, config.get_required("SMTP_PASSWORD"))
+            smtp.send_message(message)
+
+
+# healthchecks.io / Cronitor style deadman switch, a missing ping is what raises the alarm
+class HealthcheckNotifier(Notifier):
+    def __init__(self, url: str):
+        self.url = url.rstrip("/")
+
+    def notify_success(self, day: Day):
+        self.ping()
+
+    def notify_skipped(self, date: str):
+        self.ping()
+
+    def ping(self):
+        requests.get(self.url, timeout=10).raise_for_status()
+
+    def notify_failure(self, date: str, error: Exception, challenges_succeeded: int):
+        requests.post(
+            f"{self.url}/fail",
+            data=f"{date}: {type(error).__name__}: {error}".encode("utf-8"),
+            timeout=10,
+        ).raise_for_status()
+
+
+def get_notifiers() -> list[Notifier]:
+    notifiers = []
+    webhook_url = config.get("NOTIFY_WEBHOOK_URL")
+    if webhook_url:
+        notifiers.append(WebhookNotifier(webhook_url))
+    healthcheck_url = config.get("HEALTHCHECK_URL")
+    if healthcheck_url:
+        notifiers.append(HealthcheckNotifier(healthcheck_url))
+    smtp_host = config.get("SMTP_HOST")
+    if smtp_host:
+        tls = config.get("SMTP_TLS", "starttls").lower()
+        if tls not in SMTP_PORTS:
+            raise errors.ConfigError(f"SMTP_TLS {tls!r} should be one of: {', '.join(SMTP_PORTS)}")
+        notifiers.append(
+            EmailNotifier(
+                host=smtp_host,
+                port=int(config.get("SMTP_PORT", SMTP_PORTS[tls])),
+                tls=tls,
+                sender=config.get_required("NOTIFY_EMAIL_FROM"),
+                recipients=config.get_list("NOTIFY_EMAIL_TO"),
+            )
+        )
+    return notifiers
+
+
+# Notifying is best effort, a broken webhook or mail server should never take down a run
+def send_to_all(send: typing.Callable[[Notifier], None]):
+    try:
+        notifiers = get_notifiers()
+    except Exception:
+        logger.exception("Failed to configure notifiers")
+        return
+    for notifier in notifiers:
+        try:
+            send(notifier)
+        except Exception:
+            logger.exception("Failed to send notification with %s", type(notifier).__name__)
+
+
+def notify_success(day: Day):
+    send_to_all(lambda notifier: notifier.notify_success(day))
+
+
+def notify_skipped(date: str):
+    send_to_all(lambda notifier: notifier.notify_skipped(date))
+
+
+def notify_failure(date: str, error: Exception, challenges_succeeded: int):
+    send_to_all(
+        lambda notifier: notifier.notify_failure(date, error, challenges_succeeded)
+    )
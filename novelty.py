@@ -0,0 +1,83 @@
+import hashlib
+import json
+import logging
+import os
+import threading
+
+import cdn
+import config
+import keys
+import retention
+from ai import EMBEDDING_MODEL, embed
+from migrations import fetch_day, migrate_days, read_fresh_json
+from vectors import cosine_similarity
+
+NOVELTY_DAYS = 30
+NOVELTY_MAX_SIMILARITY = 0.9
+# How many times a prompt that's too close to a recent one is regenerated before it's used anyway
+NOVELTY_ATTEMPTS = 2
+CACHE_FILENAME = "prompt_embeddings.json"
+
+logger = logging.getLogger(__name__)
+
+# The chat model has favorite motifs it keeps coming back to, so a new prompt is compared against the prompts of the
+# last NOVELTY_DAYS published days. Their embeddings are cached in the output directory, keyed by model and text, so
+# each old prompt is only embedded once.
+recent_prompts: dict[tuple[str, str, str], list[str]] = {}
+lock = threading.Lock()
+
+
+def get_cache_path() -> str:
+    return os.path.join(retention.get_output_dir(), CACHE_FILENAME)
+
+
+def get_cache_key(text: str) -> str:
+    model = config.get("EMBEDDING_MODEL", EMBEDDING_MODEL)
+    return hashlib.sha256(f"{model}\n{text}".encode("utf-8")).hexdigest()
+
+
+def get_embeddings(texts: list[str]) -> list[list[float]]:
+    with lock:
+        path = get_cache_path()
+        cache = {}
+        if os.path.exists(path):
+            with open(path) as file:
+                cache = json.load(file)
+        missing = [text for text in dict.fromkeys(texts) if get_cache_key(text) not in cache]
+        if missing:
+            for text, embedding in zip(missing, embed(missing)):
+                cache[get_cache_key(text)] = embedding
+            os.makedirs(os.path.dirname(path) or ".", exist_ok=True)
+            with open(path, "w") as file:
+                json.dump(cache, file)
+        return [cache[get_cache_key(text)] for text in texts]
+
+
+# Prompts of the days published before the given one, read once per date and site (see SITES)
+def get_recent_prompts(date: str) -> list[str]:
+    cache_key = (cdn.get_bucket(), cdn.get_key_prefix(), date)
+    with lock:
+        if cache_key not in recent_prompts:
+            entries = [entry for entry in migrate_days(read_fresh_json(keys.get_key("days"))).days if entry.date < date]
+            entries = sorted(entries, key=lambda entry: entry.date)[-int(config.get("NOVELTY_DAYS", NOVELTY_DAYS)):]
+            recent_prompts[cache_key] = [
+                challenge.prompt
+                for entry in entries
+                for challenge in fetch_day(entry.date).challenges.present().values()
+            ]
+        return recent_prompts[cache_key]
+
+
+# The most similar recent prompt and how similar it is, if that's over NOVELTY_MAX_SIMILARITY
+def find_similar_prompt(prompt: str, date: str) -> tuple[float, str] | None:
+    recent = get_recent_prompts(date)
+    if not recent:
+        return None
+    embedding, *recent_embeddings = get_embeddings([prompt, *recent])
+    similarity, similar = max(
+        (cosine_similarity(embedding, recent_embedding), text)
+        for recent_embedding, text in zip(recent_embeddings, recent)
+    )
+    if similarity > float(config.get("NOVELTY_MAX_SIMILARITY", NOVELTY_MAX_SIMILARITY)):
+        return similarity, similar
+    return None
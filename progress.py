@@ -0,0 +1,45 @@
+import shutil
+import sys
+import threading
+
+import config
+
+# A status line on stderr with the stage each difficulty of each day being generated is at, so watching a run (or a
+# backfill, with several days at once) isn't just a wall of log lines. It's only drawn when stderr is a terminal, the
+# logs are left as they are either way. PROGRESS=false turns it off.
+stages: dict[str, dict[str, str]] = {}
+lock = threading.Lock()
+
+
+def is_enabled() -> bool:
+    return sys.stderr.isatty() and config.get_bool("PROGRESS", True)
+
+
+def render() -> str:
+    return "  |  ".join(
+        f"{date} " + " ".join(f"{name}:{stage}" for name, stage in day_stages.items())
+        for date, day_stages in stages.items()
+    )
+
+
+def draw():
+    width = shutil.get_terminal_size().columns
+    sys.stderr.write(f"\r\x1b[K{render()[:width - 1]}")
+    sys.stderr.flush()
+
+
+# difficulty is None for the stages that are about the whole day (title, share image, promote...)
+def update(date: str, difficulty: str | None, stage: str):
+    if not is_enabled():
+        return
+    with lock:
+        stages.setdefault(date, {})[difficulty or "day"] = stage
+        draw()
+
+
+def clear(date: str):
+    if not is_enabled():
+        return
+    with lock:
+        stages.pop(date, None)
+        draw()
@@ -0,0 +1,177 @@
+import os
+import time
+from contextlib import contextmanager
+from datetime import datetime, timezone
+
+from pydantic import BaseModel
+
+import errors
+import progress
+
+
+class StageTiming(BaseModel):
+    stage: str
+    difficulty: str | None = None
+    attempt: int
+    seconds: float
+    succeeded: bool
+
+
+class TokenUsage(BaseModel):
+    model: str
+    requests: int
+    input_tokens: int
+    output_tokens: int
+
+
+class UploadedFile(BaseModel):
+    key: str
+    url: str
+
+
+# What one of the checks on a challenge found: an image being blank or noise (image), the provider refusing the
+# prompt (rejected), the vision model missing words (critique) or guessing the wrong share of them (guessability)
+class QAOutcome(BaseModel):
+    attempt: int
+    difficulty: str
+    # Which of the challenge's image attempts it was, counting from 1, None for the checks on the final image
+    image_attempt: int | None = None
+    kind: str
+    passed: bool
+    detail: str | None = None
+
+
+# Written next to the run's output so postmortems don't have to dig through logs
+class RunReport(BaseModel):
+    run_id: str
+    command: str = "run"
+    # Where the report and the run's artifacts are written
+    output_dir: str | None = None
+    date: str
+    started_at: datetime
+    finished_at: datetime | None = None
+    succeeded: bool = False
+    attempts: int = 0
+    challenges_succeeded: int = 0
+    error: str | None = None
+    error_context: dict = {}
+    stages: list[StageTiming] = []
+    uploads: list[UploadedFile] = []
+    token_usage: list[TokenUsage] = []
+    qa: list[QAOutcome] = []
+
+    @contextmanager
+    def stage(self, name: str, difficulty: str | None = None):
+        start = time.monotonic()
+        succeeded = False
+        progress.update(self.date, difficulty, name)
+        try:
+            yield
+            succeeded = True
+        finally:
+            if not succeeded:
+                progress.update(self.date, difficulty, f"{name} failed")
+            self.stages.append(
+                StageTiming(
+                    stage=name,
+                    difficulty=difficulty,
+                    attempt=self.attempts,
+                    seconds=round(time.monotonic() - start, 3),
+                    succeeded=succeeded,
+                )
+            )
+
+    def record_upload(self, key: str, url: str):
+        self.uploads.append(UploadedFile(key=key, url=url))
+
+    def record_qa(
+        self,
+        difficulty: str,
+        kind: str,
+        passed: bool,
+        detail: str | None = None,
+        image_attempt: int | None = None,
+    ):
+        self.qa.append(
+            QAOutcome(
+                attempt=self.attempts,
+                difficulty=difficulty,
+                image_attempt=image_attempt,
+                kind=kind,
+                passed=passed,
+                detail=detail,
+            )
+        )
+
+    def finish(self, error: Exception | None = None):
+        self.finished_at = datetime.now(timezone.utc)
+        self.succeeded = error is None
+        self.error = f"{type(error).__name__}: {error}" if error else None
+        self.error_context = errors.get_context(error) if error else {}
+
+    def to_markdown(self) -> str:
+        lines = [
+            f"# Run {self.run_id} for {self.date}",
+            "",
+            f"- Result: {'succeeded' if self.succeeded else 'failed'}",
+            f"- Attempts: {self.attempts}",
+            f"- Started: {self.started_at.isoformat()}",
+            f"- Finished: {self.finished_at.isoformat() if self.finished_at else '-'}",
+        ]
+        if self.error:
+            lines.append(f"- Error: `{self.error}`")
+            if self.error_context:
+                lines.append(f"- Error context: {errors.describe_context(self.error_context)}")
+        lines += [
+            "",
+            "## Stages",
+            "",
+            "| Attempt | Difficulty | Stage | Seconds | Succeeded |",
+            "| --- | --- | --- | --- | --- |",
+        ]
+        for timing in self.stages:
+            lines.append(
+                f"| {timing.attempt} | {timing.difficulty or '-'} | {timing.stage} | {timing.seconds} | {timing.succeeded} |"
+            )
+        if self.qa:
+            lines += [
+                "",
+                "## QA",
+                "",
+                "| Attempt | Difficulty | Image attempt | Check | Passed | Detail |",
+                "| --- | --- | --- | --- | --- | --- |",
+            ]
+            for outcome in self.qa:
+                lines.append(
+                    f"| {outcome.attempt} | {outcome.difficulty} | {outcome.image_attempt or '-'} | {outcome.kind} "
+                    f"| {outcome.passed} | {outcome.detail or '-'} |"
+                )
+        if self.token_usage:
+            lines += [
+                "",
+                "## Token usage",
+                "",
+                "| Model | Requests | Input tokens | Output tokens |",
+                "| --- | --- | --- | --- |",
+            ]
+            for usage in self.token_usage:
+                lines.append(
+                    f"| {usage.model} | {usage.requests} | {usage.input_tokens} | {usage.output_tokens} |"
+                )
+        lines += ["", "## Uploads", ""]
+        for upload in self.uploads:
+            lines.append(f"- `{upload.key}`: {upload.url}")
+        return "\n".join(lines) + "\n"
+
+    def write(self, output_dir: str):
+        os.makedirs(output_dir, exist_ok=True)
+        with open(os.path.join(output_dir, "run_report.json"), "w") as file:
+            file.write(self.model_dump_json(indent=2))
+        with open(os.path.join(output_dir, "run_report.md"), "w") as file:
+            file.write(self.to_markdown())
+
+
+def start_report(date: str, run_id: str, command: str = "run") -> RunReport:
+    return RunReport(
+        run_id=run_id, command=command, date=date, started_at=datetime.now(timezone.utc)
+    )
@@ -0,0 +1,49 @@
+import logging
+import os
+import re
+import shutil
+import time
+
+import config
+
+OUTPUT_DIR = "output"
+# Only directories named like a run's are ever removed
+RUN_DIR_PATTERN = re.compile(r"\d{4}-\d{2}-\d{2}_[0-9a-f]{8}")
+
+logger = logging.getLogger(__name__)
+
+
+def get_output_dir() -> str:
+    return config.get("OUTPUT_DIR", OUTPUT_DIR)
+
+
+# Newest first
+def get_run_dirs(output_dir: str) -> list[str]:
+    if not os.path.isdir(output_dir):
+        return []
+    paths = [
+        os.path.join(output_dir, name)
+        for name in os.listdir(output_dir)
+        if RUN_DIR_PATTERN.fullmatch(name) and os.path.isdir(os.path.join(output_dir, name))
+    ]
+    return sorted(paths, key=os.path.getmtime, reverse=True)
+
+
+# Keeps at most OUTPUT_RETENTION_COUNT run directories and none older than OUTPUT_RETENTION_DAYS. With neither set
+# everything is kept.
+def clean_output() -> list[str]:
+    max_count = config.get("OUTPUT_RETENTION_COUNT")
+    max_age_days = config.get("OUTPUT_RETENTION_DAYS")
+    if max_count is None and max_age_days is None:
+        return []
+
+    cutoff = time.time() - float(max_age_days) * 24 * 60 * 60 if max_age_days is not None else None
+    removed = []
+    for index, path in enumerate(get_run_dirs(get_output_dir())):
+        too_many = max_count is not None and index >= int(max_count)
+        too_old = cutoff is not None and os.path.getmtime(path) < cutoff
+        if too_many or too_old:
+            logger.info("Removing old run output %s", path)
+            shutil.rmtree(path)
+            removed.append(path)
+    return removed
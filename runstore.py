@@ -0,0 +1,219 @@
+import json
+import os
+import sqlite3
+from contextlib import closing
+
+import config
+import retention
+from report import QAOutcome, RunReport, StageTiming, TokenUsage, UploadedFile
+
+RUN_STORE_FILENAME = "runs.sqlite3"
+
+# Every run's report, kept past the output directory's retention so there's one place to look for what happened
+SCHEMA = """
+CREATE TABLE IF NOT EXISTS runs (
+    run_id TEXT PRIMARY KEY,
+    command TEXT NOT NULL,
+    date TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    succeeded INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    challenges_succeeded INTEGER NOT NULL,
+    error TEXT,
+    error_context TEXT
+);
+CREATE TABLE IF NOT EXISTS stages (
+    run_id TEXT NOT NULL REFERENCES runs (run_id),
+    position INTEGER NOT NULL,
+    attempt INTEGER NOT NULL,
+    difficulty TEXT,
+    stage TEXT NOT NULL,
+    seconds REAL NOT NULL,
+    succeeded INTEGER NOT NULL,
+    PRIMARY KEY (run_id, position)
+);
+CREATE TABLE IF NOT EXISTS uploads (
+    run_id TEXT NOT NULL REFERENCES runs (run_id),
+    key TEXT NOT NULL,
+    url TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS token_usage (
+    run_id TEXT NOT NULL REFERENCES runs (run_id),
+    model TEXT NOT NULL,
+    requests INTEGER NOT NULL,
+    input_tokens INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL,
+    PRIMARY KEY (run_id, model)
+);
+CREATE TABLE IF NOT EXISTS qa_outcomes (
+    run_id TEXT NOT NULL REFERENCES runs (run_id),
+    position INTEGER NOT NULL,
+    attempt INTEGER NOT NULL,
+    difficulty TEXT NOT NULL,
+    image_attempt INTEGER,
+    kind TEXT NOT NULL,
+    passed INTEGER NOT NULL,
+    detail TEXT,
+    PRIMARY KEY (run_id, position)
+);
+CREATE INDEX IF NOT EXISTS runs_date ON runs (date);
+"""
+
+
+def get_path() -> str:
+    return config.get(
+        "RUN_STORE_PATH", os.path.join(retention.get_output_dir(), RUN_STORE_FILENAME)
+    )
+
+
+def connect() -> sqlite3.Connection:
+    path = get_path()
+    os.makedirs(os.path.dirname(path) or ".", exist_ok=True)
+    connection = sqlite3.connect(path)
+    connection.row_factory = sqlite3.Row
+    connection.executescript(SCHEMA)
+    return connection
+
+
+# Called when a run starts and again when it finishes, so a run that dies without finishing still shows up
+def save(report: RunReport):
+    with closing(connect()) as connection, connection:
+        connection.execute(
+            """
+            INSERT OR REPLACE INTO runs (
+                run_id, command, date, started_at, finished_at, succeeded, attempts, challenges_succeeded, error,
+                error_context
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            """,
+            (
+                report.run_id,
+                report.command,
+                report.date,
+                report.started_at.isoformat(),
+                report.finished_at.isoformat() if report.finished_at else None,
+                report.succeeded,
+                report.attempts,
+                report.challenges_succeeded,
+                report.error,
+                json.dumps(report.error_context) if report.error_context else None,
+            ),
+        )
+        connection.execute("DELETE FROM stages WHERE run_id = ?", (report.run_id,))
+        connection.executemany(
+            """
+            INSERT INTO stages (run_id, position, attempt, difficulty, stage, seconds, succeeded)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            """,
+            [
+                (
+                    report.run_id,
+                    position,
+                    timing.attempt,
+                    timing.difficulty,
+                    timing.stage,
+                    timing.seconds,
+                    timing.succeeded,
+                )
+                for position, timing in enumerate(report.stages)
+            ],
+        )
+        connection.execute("DELETE FROM uploads WHERE run_id = ?", (report.run_id,))
+        connection.executemany(
+            "INSERT INTO uploads (run_id, key, url) VALUES (?, ?, ?)",
+            [(report.run_id, upload.key, upload.url) for upload in report.uploads],
+        )
+        connection.execute("DELETE FROM token_usage WHERE run_id = ?", (report.run_id,))
+        connection.executemany(
+            """
+            INSERT INTO token_usage (run_id, model, requests, input_tokens, output_tokens)
+            VALUES (?, ?, ?, ?, ?)
+            """,
+            [
+                (report.run_id, usage.model, usage.requests, usage.input_tokens, usage.output_tokens)
+                for usage in report.token_usage
+            ],
+        )
+        connection.execute("DELETE FROM qa_outcomes WHERE run_id = ?", (report.run_id,))
+        connection.executemany(
+            """
+            INSERT INTO qa_outcomes (run_id, position, attempt, difficulty, image_attempt, kind, passed, detail)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            """,
+            [
+                (
+                    report.run_id,
+                    position,
+                    outcome.attempt,
+                    outcome.difficulty,
+                    outcome.image_attempt,
+                    outcome.kind,
+                    outcome.passed,
+                    outcome.detail,
+                )
+                for position, outcome in enumerate(report.qa)
+            ],
+        )
+
+
+def list_runs(limit: int, failed_only: bool = False) -> list[sqlite3.Row]:
+    with closing(connect()) as connection:
+        return connection.execute(
+            f"""
+            SELECT * FROM runs {"WHERE NOT succeeded" if failed_only else ""}
+            ORDER BY started_at DESC LIMIT ?
+            """,
+            (limit,),
+        ).fetchall()
+
+
+def get_report(run_id: str) -> RunReport | None:
+    with closing(connect()) as connection:
+        run = connection.execute("SELECT * FROM runs WHERE run_id = ?", (run_id,)).fetchone()
+        if run is None:
+            return None
+        stages = connection.execute(
+            "SELECT * FROM stages WHERE run_id = ? ORDER BY position", (run_id,)
+        ).fetchall()
+        uploads = connection.execute("SELECT * FROM uploads WHERE run_id = ?", (run_id,)).fetchall()
+        token_usage = connection.execute(
+            "SELECT * FROM token_usage WHERE run_id = ? ORDER BY model", (run_id,)
+        ).fetchall()
+        qa_outcomes = connection.execute(
+            "SELECT * FROM qa_outcomes WHERE run_id = ? ORDER BY position", (run_id,)
+        ).fetchall()
+    return RunReport(
+        **{key: run[key] for key in run.keys() if key != "error_context"},
+        error_context=json.loads(run["error_context"]) if run["error_context"] else {},
+        stages=[
+            StageTiming(
+                stage=stage["stage"],
+                difficulty=stage["difficulty"],
+                attempt=stage["attempt"],
+                seconds=stage["seconds"],
+                succeeded=stage["succeeded"],
+            )
+            for stage in stages
+        ],
+        uploads=[UploadedFile(key=upload["key"], url=upload["url"]) for upload in uploads],
+        token_usage=[
+            TokenUsage(
+                model=usage["model"],
+                requests=usage["requests"],
+                input_tokens=usage["input_tokens"],
+                output_tokens=usage["output_tokens"],
+            )
+            for usage in token_usage
+        ],
+        qa=[
+            QAOutcome(
+                attempt=outcome["attempt"],
+                difficulty=outcome["difficulty"],
+                image_attempt=outcome["image_attempt"],
+                kind=outcome["kind"],
+                passed=outcome["passed"],
+                detail=outcome["detail"],
+            )
+            for outcome in qa_outcomes
+        ],
+    )
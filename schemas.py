@@ -0,0 +1,56 @@
+import json
+import logging
+import os
+
+from models import (
+    Challenge,
+    Day,
+    DaySignature,
+    Days,
+    DaysManifest,
+    DayVectors,
+    MonthArchive,
+    PublishedModel,
+    SCHEMA_VERSION,
+    Stats,
+)
+
+SCHEMA_DIR = "schemas"
+JSON_SCHEMA_DIALECT = "https://json-schema.org/draft/2020-12/schema"
+
+# Every file the frontend reads, by the name of its schema file. The sidecars are the .sig and .vectors.json files
+# next to each day, the shards of days.json and its manifest, the monthly archive and stats.json.
+SCHEMAS: dict[str, type[PublishedModel]] = {
+    "day": Day,
+    "days": Days,
+    "challenge": Challenge,
+    "day_signature": DaySignature,
+    "day_vectors": DayVectors,
+    "days_manifest": DaysManifest,
+    "month_archive": MonthArchive,
+    "stats": Stats,
+}
+
+logger = logging.getLogger(__name__)
+
+
+# In serialization mode, so the schema describes the JSON as it's published rather than what would be accepted
+def build_schema(model: type[PublishedModel]) -> dict:
+    return {
+        "$schema": JSON_SCHEMA_DIALECT,
+        **model.model_json_schema(mode="serialization"),
+        "x-schema-version": SCHEMA_VERSION,
+    }
+
+
+# Writes <name>.schema.json for each published model and returns the paths
+def export_schemas(directory: str = SCHEMA_DIR) -> list[str]:
+    os.makedirs(directory, exist_ok=True)
+    paths = []
+    for name, model in SCHEMAS.items():
+        path = os.path.join(directory, f"{name}.schema.json")
+        with open(path, "w") as file:
+            file.write(json.dumps(build_schema(model), indent=2) + "\n")
+        logger.info("Wrote %s", path)
+        paths.append(path)
+    return paths
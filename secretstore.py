@@ -0,0 +1,61 @@
+import json
+
+import boto3
+import requests
+
+import config
+
+# Config values can point at a secret instead of holding it, e.g.
+#   aws-sm://iamdreamingof/openai            whole SecretString
+#   aws-sm://iamdreamingof/keys#openai       one key of a JSON SecretString
+#   vault://secret/data/iamdreamingof#openai one key of a Vault KV secret (v1 or v2)
+AWS_SECRETS_MANAGER_SCHEME = "aws-sm://"
+VAULT_SCHEME = "vault://"
+
+resolved_secrets: dict[str, str] = {}
+
+
+def is_reference(value) -> bool:
+    return isinstance(value, str) and value.startswith(
+        (AWS_SECRETS_MANAGER_SCHEME, VAULT_SCHEME)
+    )
+
+
+def split_reference(reference: str, scheme: str) -> tuple[str, str | None]:
+    path, _, key = reference.removeprefix(scheme).partition("#")
+    return path, key or None
+
+
+def read_aws_secret(reference: str) -> str:
+    secret_id, key = split_reference(reference, AWS_SECRETS_MANAGER_SCHEME)
+    client = boto3.session.Session().client("secretsmanager")
+    secret = client.get_secret_value(SecretId=secret_id)["SecretString"]
+    if key is None:
+        return secret
+    return json.loads(secret)[key]
+
+
+def read_vault_secret(reference: str) -> str:
+    path, key = split_reference(reference, VAULT_SCHEME)
+    response = requests.get(
+        f'{config.get_required("VAULT_ADDR").rstrip("/")}/v1/{path}',
+        headers={"X-Vault-Token": config.get_required("VAULT_TOKEN")},
+        timeout=10,
+    )
+    response.raise_for_status()
+    data = response.json()["data"]
+    # KV v2 nests the secret one level deeper
+    if isinstance(data.get("data"), dict):
+        data = data["data"]
+    return data[key or "value"]
+
+
+def resolve(value):
+    if not is_reference(value):
+        return value
+    if value not in resolved_secrets:
+        if value.startswith(AWS_SECRETS_MANAGER_SCHEME):
+            resolved_secrets[value] = read_aws_secret(value)
+        else:
+            resolved_secrets[value] = read_vault_secret(value)
+    return resolved_secrets[value]
@@ -0,0 +1,57 @@
+import base64
+import binascii
+
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+
+import config
+import errors
+from models import DaySignature
+
+# Optional Ed25519 signatures for the published day JSON, so clients can tell a day wasn't changed on the CDN or a
+# mirror. DAY_SIGNING_KEY is a base64 encoded 32 byte seed, i.e. from `openssl rand -base64 32`, and each day gets a
+# days/<date>.json.sig sidecar.
+SEED_LENGTH = 32
+
+
+def get_public_key(seed: bytes) -> bytes:
+    return Ed25519PrivateKey.from_private_bytes(seed).public_key().public_bytes(Encoding.Raw, PublicFormat.Raw)
+
+
+def sign_bytes(seed: bytes, message: bytes) -> bytes:
+    return Ed25519PrivateKey.from_private_bytes(seed).sign(message)
+
+
+def get_signature_key(key: str) -> str:
+    return f"{key}.sig"
+
+
+# The day JSON to publish at key, along with its signature sidecar when signing is configured
+def get_signed_files(key: str, data: str) -> dict[str, str]:
+    files = {key: data}
+    signature = sign(data)
+    if signature is not None:
+        files[get_signature_key(key)] = signature.model_dump_json()
+    return files
+
+
+def decode_seed(encoded_seed: str) -> bytes:
+    try:
+        seed = base64.b64decode(encoded_seed, validate=True)
+    except (binascii.Error, ValueError) as e:
+        raise errors.ConfigError(f"DAY_SIGNING_KEY isn't valid base64: {e}") from e
+    if len(seed) != SEED_LENGTH:
+        raise errors.ConfigError(f"DAY_SIGNING_KEY should be {SEED_LENGTH} bytes, it's {len(seed)}")
+    return seed
+
+
+# None when signing isn't configured
+def sign(data: str) -> DaySignature | None:
+    encoded_seed = config.get("DAY_SIGNING_KEY")
+    if not encoded_seed:
+        return None
+    seed = decode_seed(encoded_seed)
+    return DaySignature(
+        public_key=base64.b64encode(get_public_key(seed)).decode("ascii"),
+        signature=base64.b64encode(sign_bytes(seed, data.encode("utf-8"))).decode("ascii"),
+    )
@@ -0,0 +1,29 @@
+from xml.sax.saxutils import escape
+
+import config
+import indexes
+from models import Days
+
+SITE_URL = "https://iamdreamingof.com"
+DAY_URL_TEMPLATE = "https://iamdreamingof.com/days/{date}"
+
+
+def build_sitemap(days: Days) -> str:
+    day_url_template = config.get("DAY_URL_TEMPLATE", DAY_URL_TEMPLATE)
+    urls = [f"  <url><loc>{escape(config.get('SITE_URL', SITE_URL))}</loc></url>"]
+    for entry in sorted(days.days, key=lambda entry: entry.date):
+        if not indexes.is_live(entry.publish_at):
+            continue
+        url = day_url_template.format(date=entry.date, id=entry.id)
+        urls.append(
+            f"  <url><loc>{escape(url)}</loc><lastmod>{entry.date}</lastmod></url>"
+        )
+    return "\n".join(
+        [
+            '<?xml version="1.0" encoding="UTF-8"?>',
+            '<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">',
+            *urls,
+            "</urlset>",
+            "",
+        ]
+    )
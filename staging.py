@@ -0,0 +1,105 @@
+import logging
+
+import cdn
+
+STAGING_PREFIX = "staging"
+
+logger = logging.getLogger(__name__)
+
+
+# Everything a day is made of (images, the day JSON, its vectors) is uploaded privately under staging/<id>/ first and
+# only copied to its real key once all of it made it. Whatever is then published (the staged files, and the indexes
+# after them) is first backed up under staging/<id>.backup/ if it already exists, so a failure part way through can be
+# rolled back: files that were new are deleted again and files that were replaced get their old contents back.
+class Staging:
+    def __init__(self, staging_id: str):
+        self.prefix = f"{STAGING_PREFIX}/{staging_id}"
+        self.backup_prefix = f"{STAGING_PREFIX}/{staging_id}.backup"
+        # Real key to staged key, in upload order
+        self.staged: dict[str, str] = {}
+        # Real keys that were (or are about to be) published, in order
+        self.published: list[str] = []
+        # Real key to the backup of what it held before, for the published keys that already existed
+        self.backups: dict[str, str] = {}
+
+    def get_staged_key(self, key: str) -> str:
+        return f"{self.prefix}/{key}"
+
+    # Returns the URL the file will have once promoted
+    def upload_file(self, path: str, key: str) -> str:
+        cdn.upload_file(path, self.get_staged_key(key), public=False)
+        self.staged[key] = self.get_staged_key(key)
+        return cdn.get_url(key)
+
+    def upload_bytes(self, data: bytes, key: str, content_type: str) -> str:
+        cdn.upload_bytes(data, self.get_staged_key(key), content_type, public=False)
+        self.staged[key] = self.get_staged_key(key)
+        return cdn.get_url(key)
+
+    def upload_json(self, data: str, key: str) -> str:
+        cdn.upload_json(data, self.get_staged_key(key), public=False)
+        self.staged[key] = self.get_staged_key(key)
+        return cdn.get_url(key)
+
+    # Call before writing the keys by other means, so they're part of what a roll back undoes
+    def protect(self, keys: list[str]):
+        for key in keys:
+            if key in self.published:
+                continue
+            if cdn.exists(key):
+                backup_key = f"{self.backup_prefix}/{key}"
+                cdn.copy(key, backup_key, public=False)
+                self.backups[key] = backup_key
+            self.published.append(key)
+
+    # Copies everything staged to its real key and returns those keys and URLs
+    def promote(self) -> dict[str, str]:
+        promoted = {}
+        for key, staged_key in self.staged.items():
+            self.protect([key])
+            logger.info("Promoting %s", key)
+            promoted[key] = cdn.copy(staged_key, key)
+        self.remove(list(self.staged.values()))
+        self.staged = {}
+        return promoted
+
+    # Everything's published, so there's nothing left to roll back
+    def finish(self):
+        self.published = []
+        self.clean_up()
+
+    # Puts back what was there before anything was published. Only meant for a failed attempt.
+    def roll_back(self):
+        if not self.published:
+            return
+        logger.info("Rolling back %s published files", len(self.published))
+        new_keys = []
+        for key in reversed(self.published):
+            if key in self.backups:
+                logger.info("Restoring %s", key)
+                cdn.copy(self.backups[key], key)
+            else:
+                new_keys.append(key)
+        if new_keys:
+            logger.info("Removing %s new files", len(new_keys))
+            cdn.delete(new_keys)
+        self.published = []
+
+    # The backups are kept if a roll back didn't finish, they're what's needed to put things right by hand
+    def clean_up(self):
+        if self.published:
+            logger.warning(
+                "%s published files weren't rolled back, keeping their backups under %s",
+                len(self.published),
+                self.backup_prefix,
+            )
+            self.remove(list(self.staged.values()))
+        else:
+            self.remove(list(self.staged.values()) + list(self.backups.values()))
+            self.backups = {}
+        self.staged = {}
+
+    def remove(self, keys: list[str]):
+        if keys:
+            logger.info("Removing %s staged files", len(keys))
+            cdn.delete(keys)
@@ -0,0 +1,80 @@
+import logging
+from collections import Counter
+from datetime import datetime, timezone
+
+import cdn
+import indexes
+import keys
+from migrations import fetch_day
+from models import SCHEMA_VERSION, Day, Days, Stats
+
+logger = logging.getLogger(__name__)
+
+
+def empty_stats() -> Stats:
+    return Stats(
+        generated_at=datetime.now(timezone.utc),
+        days_published=0,
+        challenges_published=0,
+        word_usage={},
+        models={},
+        providers={},
+    )
+
+
+def count_day(stats: Stats, day: Day):
+    for challenge in day.challenges.present().values():
+        stats.challenges_published += 1
+        for word in challenge.words:
+            counts = stats.word_usage.setdefault(word.type, {})
+            counts[word.word] = counts.get(word.word, 0) + 1
+    if day.meta is not None:
+        if day.meta.provider:
+            stats.providers[day.meta.provider] = stats.providers.get(day.meta.provider, 0) + 1
+        for capabilities in day.meta.models.values():
+            for capability, model in capabilities.items():
+                counts = stats.models.setdefault(capability, {})
+                counts[model] = counts.get(model, 0) + 1
+
+
+# Read from the bucket rather than the CDN, which may still be serving a copy from before the last run
+def fetch_previous_stats() -> Stats | None:
+    try:
+        previous = Stats.model_validate(cdn.read_json(keys.get_key("stats")))
+    except Exception:
+        logger.warning("Failed to read the previous stats, counting every day again", exc_info=True)
+        return None
+    if previous.schema_version != SCHEMA_VERSION or len(previous.dates) != previous.days_published:
+        logger.info("The previous stats can't be added to, counting every day again")
+        return None
+    return previous
+
+
+# The previous stats plus the live days they don't count yet, with the day that was just generated passed in as the
+# CDN may still be serving an older copy of it. A day already counted can't be taken back out, so if the current day
+# is one (it was generated again) or a counted day is no longer live, every day is counted again.
+def build_stats(days: Days, current_day: Day, previous: Stats | None = None) -> Stats:
+    live_dates = [entry.date for entry in days.days if indexes.is_live(entry.publish_at)]
+    if previous is not None and (current_day.date in previous.dates or not set(previous.dates) <= set(live_dates)):
+        previous = None
+    stats = previous.model_copy(deep=True) if previous is not None else empty_stats()
+
+    counted = set(stats.dates)
+    new_dates = [date for date in live_dates if date not in counted]
+    logger.info("Adding %s days to the stats", len(new_dates))
+    for date in new_dates:
+        count_day(stats, current_day if date == current_day.date else fetch_day(date))
+
+    stats.generated_at = datetime.now(timezone.utc)
+    stats.dates = sorted(counted | set(new_dates))
+    stats.days_published = len(stats.dates)
+    stats.word_usage = {
+        word_type: dict(Counter(counts).most_common()) for word_type, counts in stats.word_usage.items()
+    }
+    return stats
+
+
+# Returns the uploaded key and its URL
+def publish_stats(days: Days, current_day: Day) -> tuple[str, str]:
+    key = keys.get_key("stats")
+    return key, cdn.upload_json(build_stats(days, current_day, fetch_previous_stats()).model_dump_json(), key)
@@ -0,0 +1,10 @@
+import hashlib
+
+from words import import_json_wordlist
+
+
+# Picks the art direction for a day. Hashing the date keeps it stable across retries and regenerations.
+def get_style_for_day(day: str) -> str:
+    styles = import_json_wordlist("styles.json")
+    index = int(hashlib.sha256(day.encode("utf-8")).hexdigest(), 16) % len(styles)
+    return styles[index]
@@ -0,0 +1,78 @@
+import os
+import tempfile
+import unittest
+from datetime import datetime, timezone
+from unittest import mock
+
+import config
+import main
+from ai import GeneratedPrompt, ImageCritique
+from errors import QualityError
+from models import Word
+from report import RunReport
+
+WORDS = [
+    Word(word="apples", type="object"),
+    Word(word="dancing", type="gerund"),
+    Word(word="anger", type="concept"),
+]
+
+
+def generate_candidates(prompt, style, words, difficulty, artifact_dir, run_report, image_attempt):
+    path = os.path.join(artifact_dir, "image.png")
+    with open(path, "w") as file:
+        file.write(prompt)
+    return [path], None
+
+
+# Every image leaves out the anger
+def critique_image(image_path, words, difficulty=None):
+    return ImageCritique(description="Apples dancing", shown=["apples", "dancing"], critique="Show the anger")
+
+
+@mock.patch.multiple(
+    main,
+    generate_candidates=generate_candidates,
+    critique_image=critique_image,
+    revise_prompt=lambda prompt, words, critique, difficulty: prompt.model_copy(
+        update={"description": f"{prompt.description} Angrily."}
+    ),
+)
+@mock.patch("main.time.sleep")
+class ChallengeImageTest(unittest.TestCase):
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.artifact_dir = directory.name
+        self.run_report = RunReport(run_id="run", date="2024-06-01", started_at=datetime.now(timezone.utc))
+        self.prompt = GeneratedPrompt(description="Apples dancing.", title="The Orchard Ball", hint="Fruit")
+        for key, value in {"IMAGE_ATTEMPTS": 2, "IMAGE_ATTEMPT_DELAY": 5, "IMAGE_CRITIQUE": True}.items():
+            config.set_override(key, value)
+        self.addCleanup(config.overrides.clear)
+
+    def generate(self) -> GeneratedPrompt:
+        return main.generate_challenge_image(
+            self.prompt, WORDS, "a watercolor painting", "easy", self.artifact_dir, self.run_report
+        )
+
+    def test_uses_the_last_image_anyway(self, sleep):
+        generated_prompt = self.generate()
+        self.assertEqual(generated_prompt.description, "Apples dancing. Angrily.")
+        with open(os.path.join(self.artifact_dir, "image.png")) as file:
+            self.assertEqual(file.read(), "Apples dancing. Angrily.")
+        sleep.assert_called_once_with(5.0)
+        self.assertEqual(
+            [(outcome.image_attempt, outcome.kind, outcome.passed) for outcome in self.run_report.qa],
+            [(1, "critique", False), (2, "critique", False)],
+        )
+        self.assertEqual(self.run_report.qa[0].detail, "missing anger: Show the anger")
+
+    def test_strict(self, sleep):
+        config.set_override("IMAGE_STRICT", True)
+        with self.assertRaisesRegex(QualityError, "still didn't show anger after 2 attempts"):
+            self.generate()
+        sleep.assert_called_once_with(5.0)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,33 @@
+import os
+import tempfile
+import unittest
+from unittest import mock
+
+import config
+import errors
+
+
+class LoadTest(unittest.TestCase):
+    def load(self, contents: str):
+        with tempfile.NamedTemporaryFile("w", suffix=".toml", delete=False) as file:
+            file.write(contents)
+        self.addCleanup(os.remove, file.name)
+        self.addCleanup(config.file_values.clear)
+        config.load(file.name)
+
+    def test_image_attempts(self):
+        self.load("image_attempts = 3\n")
+        self.assertEqual(config.get("IMAGE_ATTEMPTS"), 3)
+
+    def test_no_image_attempts(self):
+        for contents in ["image_attempts = 0\n", 'image_attempts = "none"\n']:
+            with self.subTest(contents=contents), self.assertRaises(errors.ConfigError):
+                self.load(contents)
+
+    def test_no_image_attempts_from_environment(self):
+        with mock.patch.dict(os.environ, {"IMAGE_ATTEMPTS": "0"}), self.assertRaises(errors.ConfigError):
+            self.load("")
+
+
+if __name__ == "__main__":
+    unittest.main()
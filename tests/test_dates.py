@@ -0,0 +1,42 @@
+import argparse
+import unittest
+from unittest import mock
+
+from errors import EXIT_ARGUMENTS, ArgumentError, get_exit_code
+from main import check_date, get_dates, parse_date
+
+
+class ParseDateTest(unittest.TestCase):
+    def test_accepts_full_dates(self):
+        self.assertEqual(parse_date("2024-06-01"), "2024-06-01")
+
+    def test_rejects_bad_dates(self):
+        for value in ["2024-6-1", "2024-02-30", "20240601", "tomorrow"]:
+            with self.subTest(value=value), self.assertRaises(argparse.ArgumentTypeError):
+                parse_date(value)
+
+
+@mock.patch("main.get_today_str", return_value="2024-06-01")
+class CheckDateTest(unittest.TestCase):
+    def setUp(self):
+        settings = {"SITE_LAUNCH_DATE": "2024-01-01"}
+        patcher = mock.patch("main.config.get", side_effect=lambda key, default=None: settings.get(key, default))
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_before_launch(self, _):
+        with self.assertRaises(ArgumentError) as caught:
+            check_date("2023-12-31")
+        self.assertEqual(get_exit_code(caught.exception), EXIT_ARGUMENTS)
+
+    def test_far_future(self, _):
+        self.assertEqual(check_date("2024-06-08", True), "2024-06-08")
+        self.assertEqual(check_date("2024-07-01"), "2024-07-01")
+        with self.assertRaises(ArgumentError):
+            check_date("2024-06-09", True)
+        self.assertEqual(check_date("2024-06-09", True, allow_future=True), "2024-06-09")
+
+    def test_get_dates(self, _):
+        self.assertEqual(get_dates("2024-05-30", "2024-06-01"), ["2024-05-30", "2024-05-31", "2024-06-01"])
+        with self.assertRaises(ArgumentError):
+            get_dates("2024-06-01", "2024-05-30")
@@ -0,0 +1,36 @@
+import unittest
+
+from errors import FatalError
+from garbage import get_key_for_url, get_template_pattern
+
+
+class TemplatePatternTest(unittest.TestCase):
+    def test_default_image_template(self):
+        pattern = get_template_pattern("{date}/{filename}")
+        self.assertTrue(pattern.fullmatch("2024-06-01/0b7e9a4c.jpg"))
+        self.assertFalse(pattern.fullmatch("og/2024-06-01.jpg"))
+        self.assertFalse(pattern.fullmatch("days/2024-06-01.json"))
+        self.assertFalse(pattern.fullmatch("staging/abc-1/2024-06-01/0b7e9a4c.jpg"))
+
+    def test_nested_image_template(self):
+        pattern = get_template_pattern("images/{year}/{date}/{filename}")
+        self.assertTrue(pattern.fullmatch("images/2024/2024-06-01/0b7e9a4c.webp"))
+        self.assertFalse(pattern.fullmatch("images/2024/0b7e9a4c.webp"))
+
+
+class KeyForUrlTest(unittest.TestCase):
+    def test_under_base_url(self):
+        key = get_key_for_url("https://cdn.iamdreamingof.com/2024-06-01/a.jpg", "https://cdn.iamdreamingof.com/")
+        self.assertEqual(key, "2024-06-01/a.jpg")
+
+    def test_other_base_url(self):
+        for url in (
+            "https://old-cdn.iamdreamingof.com/2024-06-01/a.jpg",
+            "https://cdn.iamdreamingof.com/env/prod/2024-06-01/a.jpg",
+        ):
+            with self.assertRaises(FatalError):
+                get_key_for_url(url, "https://cdn.iamdreamingof.com/env/staging/")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,46 @@
+import os
+import tempfile
+import unittest
+
+from wand.color import Color
+from wand.image import Image
+
+from image import CHECK_IMAGE_SIZE, find_image_problem
+
+
+class ImageProblemTest(unittest.TestCase):
+    def setUp(self):
+        self.directory = tempfile.TemporaryDirectory()
+        self.addCleanup(self.directory.cleanup)
+
+    def save(self, img: Image, name: str) -> str:
+        path = os.path.join(self.directory.name, f"{name}.png")
+        img.save(filename=path)
+        return path
+
+    def test_flat(self):
+        with Image(width=CHECK_IMAGE_SIZE, height=CHECK_IMAGE_SIZE, background=Color("#808080")) as img:
+            path = self.save(img, "flat")
+        self.assertRegex(find_image_problem(path), "^it's a single color")
+
+    def test_noise(self):
+        with Image(width=CHECK_IMAGE_SIZE, height=CHECK_IMAGE_SIZE, background=Color("#808080")) as img:
+            img.noise("random", attenuate=1.0)
+            path = self.save(img, "noise")
+        self.assertRegex(find_image_problem(path), "^it's noise")
+
+    def test_picture(self):
+        with Image(width=CHECK_IMAGE_SIZE, height=CHECK_IMAGE_SIZE, pseudo="gradient:navy-orange") as img:
+            path = self.save(img, "gradient")
+        self.assertIsNone(find_image_problem(path))
+
+    # A real picture's fine grain shouldn't be mistaken for static
+    def test_grainy_picture(self):
+        with Image(width=CHECK_IMAGE_SIZE, height=CHECK_IMAGE_SIZE, pseudo="gradient:navy-orange") as img:
+            img.noise("gaussian", attenuate=0.3)
+            path = self.save(img, "grainy")
+        self.assertIsNone(find_image_problem(path))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,44 @@
+import logging
+import os
+import tempfile
+import unittest
+
+import main
+
+
+class LogFileTest(unittest.TestCase):
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.addCleanup(main.set_log_file, None)
+        self.path = os.path.join(directory.name, "run.log")
+        self.other_path = os.path.join(directory.name, "other.log")
+
+    def read(self, path: str) -> str:
+        if main.log_file_handler is not None:
+            main.log_file_handler.flush()
+        with open(path) as file:
+            return file.read()
+
+    def test_has_every_modules_logs(self):
+        main.set_log_file(self.path)
+        logging.getLogger("staging").warning("Rolling back")
+        self.assertIn("Rolling back", self.read(self.path))
+
+    def test_kept_across_sites(self):
+        main.set_log_file(self.path)
+        handler = main.log_file_handler
+        main.set_log_file(self.path)
+        self.assertIs(main.log_file_handler, handler)
+        self.assertEqual(logging.getLogger().handlers.count(handler), 1)
+
+        main.set_log_file(self.other_path)
+        self.assertNotIn(handler, logging.getLogger().handlers)
+        self.assertIsNone(handler.stream)
+        logging.getLogger("cdn").warning("Uploading")
+        self.assertIn("Uploading", self.read(self.other_path))
+        self.assertNotIn("Uploading", self.read(self.path))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,161 @@
+import json
+import os
+import unittest
+from datetime import datetime, timezone
+
+from errors import FatalError
+from migrations import migrate_day, migrate_days
+from models import (
+    SCHEMA_VERSION,
+    Challenge,
+    Challenges,
+    DateEntry,
+    Day,
+    Days,
+    GenerationMeta,
+    TemplateVersions,
+    Word,
+    WordHints,
+)
+
+GOLDEN_DIR = os.path.join(os.path.dirname(__file__), "golden")
+
+# The frontend reads these files, so a renamed or retyped field has to be a deliberate change. Run with
+# UPDATE_GOLDEN=1 to rewrite the golden files after one.
+UPDATE_GOLDEN = os.environ.get("UPDATE_GOLDEN") == "1"
+
+
+def make_challenge(difficulty: str) -> Challenge:
+    return Challenge(
+        difficulty=difficulty,
+        words=[
+            Word(word="apples", type="object"),
+            Word(word="dancing", type="gerund"),
+            Word(word="anger", type="concept"),
+        ],
+        image_path=f"/tmp/{difficulty}",
+        image_url_jpg=f"https://cdn.iamdreamingof.com/2024-06-01/{difficulty}.jpg",
+        image_url_webp=f"https://cdn.iamdreamingof.com/2024-06-01/{difficulty}.webp",
+        image_sha256_jpg="9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        image_sha256_webp="60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752",
+        image_bytes_jpg=84213,
+        image_bytes_webp=51877,
+        prompt="Apples waltz across a furious red sky.",
+        title="The Orchard Ball",
+        hint="Fruit, movement and a hot temper.",
+        hints=[WordHints(word="apples", hints=["It grows", "It's fruit", "Keeps doctors away"])],
+        decoys=[Word(word="pears", type="object")],
+        difficulty_score=0.5,
+    )
+
+
+def make_day() -> Day:
+    return Day(
+        date="2024-06-01",
+        id=42,
+        challenges=Challenges(
+            easy=make_challenge("easy"),
+            medium=make_challenge("medium"),
+            hard=make_challenge("hard"),
+            dreaming=make_challenge("dreaming"),
+        ),
+        title="Where the Orchards Dance",
+        style="a watercolor painting",
+        share_image_url="https://cdn.iamdreamingof.com/og/2024-06-01.jpg",
+        meta=GenerationMeta(
+            generated_at=datetime(2024, 6, 1, 0, 15, tzinfo=timezone.utc),
+            generator_version="0.1.0",
+            provider="api.openai.com",
+            models={"easy": {"chat": "gpt-4", "image": "dall-e-3"}},
+            attempts=1,
+            templates=TemplateVersions(prompt=2, image=3),
+            prompt_variants={"easy": "a"},
+        ),
+    )
+
+
+def make_days() -> Days:
+    return Days(
+        days=[
+            DateEntry(date="2024-05-31", id=41),
+            DateEntry(
+                date="2024-06-01",
+                id=42,
+                sha256="9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+                updated_at=datetime(2024, 6, 1, 4, 0, tzinfo=timezone.utc),
+            ),
+        ]
+    )
+
+
+class GoldenFileTest(unittest.TestCase):
+    def assert_matches_golden(self, name: str, serialized: str):
+        path = os.path.join(GOLDEN_DIR, name)
+        if UPDATE_GOLDEN:
+            with open(path, "w") as file:
+                file.write(serialized + "\n")
+        with open(path) as file:
+            self.assertEqual(json.loads(file.read()), json.loads(serialized))
+
+    def test_day(self):
+        self.assert_matches_golden("day.json", make_day().model_dump_json(indent=2))
+
+    def test_days(self):
+        self.assert_matches_golden("days.json", make_days().model_dump_json(indent=2))
+
+    def test_challenge(self):
+        self.assert_matches_golden(
+            "challenge.json", make_challenge("dreaming").model_dump_json(indent=2)
+        )
+
+    def test_partial_day(self):
+        day = make_day().model_copy(update={"missing": ["hard"]})
+        day.challenges = day.challenges.model_copy(update={"hard": None})
+        self.assert_matches_golden("partial_day.json", day.model_dump_json(indent=2))
+        self.assertEqual(list(day.challenges.present()), ["easy", "medium", "dreaming"])
+
+    def test_day_round_trips(self):
+        with open(os.path.join(GOLDEN_DIR, "day.json")) as file:
+            self.assertEqual(migrate_day(json.load(file)), make_day())
+
+
+# Payloads in the shape the generator published before any of the optional fields or schema_version existed
+class LegacyPayloadTest(unittest.TestCase):
+    def test_legacy_day(self):
+        with open(os.path.join(GOLDEN_DIR, "legacy_day.json")) as file:
+            day = migrate_day(json.load(file))
+        self.assertEqual(day.id, 7)
+        self.assertEqual(day.challenges.dreaming.words[2].type, "concept")
+        self.assertEqual(day.challenges.dreaming.difficulty, "dreaming")
+        self.assertIsNone(day.meta)
+        self.assertEqual(day.schema_version, SCHEMA_VERSION)
+
+    def test_legacy_days(self):
+        with open(os.path.join(GOLDEN_DIR, "legacy_days.json")) as file:
+            days = migrate_days(json.load(file))
+        self.assertEqual([entry.id for entry in days.days], [0, 1])
+
+    def test_invalid_ids(self):
+        for day_id in (-1, 2**32):
+            with self.assertRaises(FatalError):
+                migrate_days({"days": [{"date": "2024-06-01", "id": day_id}]})
+
+    def test_unknown_field(self):
+        with self.assertRaises(FatalError):
+            migrate_days({"schema_version": 1, "days": [{"date": "2024-06-01", "id": 0, "color": "red"}]})
+
+    def test_newer_schema_version(self):
+        with self.assertRaisesRegex(FatalError, f"schema version {SCHEMA_VERSION + 1}"):
+            migrate_days({"schema_version": SCHEMA_VERSION + 1, "days": []})
+
+    # Version 1 days were published without each challenge's difficulty
+    def test_day_1_to_2(self):
+        payload = json.loads(make_day().model_dump_json())
+        payload["schema_version"] = 1
+        for challenge in payload["challenges"].values():
+            del challenge["difficulty"]
+        self.assertEqual(migrate_day(payload), make_day())
+
+
+if __name__ == "__main__":
+    unittest.main()
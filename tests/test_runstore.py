@@ -0,0 +1,32 @@
+import os
+import tempfile
+import unittest
+
+import config
+import runstore
+from report import start_report
+
+
+class RunStoreTest(unittest.TestCase):
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        config.set_override("RUN_STORE_PATH", os.path.join(directory.name, "runs.sqlite3"))
+        self.addCleanup(config.overrides.pop, "RUN_STORE_PATH")
+
+    def test_keeps_qa_outcomes(self):
+        report = start_report("2024-06-01", "run")
+        report.attempts = 1
+        report.record_qa("easy", "image", False, "it's noise (blurring keeps 0.12 of its deviation)", 1)
+        report.record_qa("easy", "critique", False, "missing anger: Show the anger", 2)
+        report.record_qa("easy", "critique", True, image_attempt=3)
+        report.record_qa("easy", "guessability", True, "0.667 (fine)")
+        runstore.save(report)
+
+        saved = runstore.get_report("run")
+        self.assertEqual(saved.qa, report.qa)
+        self.assertIn("| 1 | easy | 2 | critique | False | missing anger: Show the anger |", saved.to_markdown())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,18 @@
+import unittest
+
+import schemas
+from models import SCHEMA_VERSION, Day
+
+
+class SchemaTest(unittest.TestCase):
+    def test_day_schema(self):
+        schema = schemas.build_schema(Day)
+        self.assertEqual(schema["$schema"], schemas.JSON_SCHEMA_DIALECT)
+        self.assertEqual(schema["x-schema-version"], SCHEMA_VERSION)
+        self.assertFalse(schema["additionalProperties"])
+        self.assertIn("challenges", schema["required"])
+        self.assertEqual(schema["properties"]["id"]["minimum"], 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
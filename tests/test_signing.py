@@ -0,0 +1,49 @@
+import base64
+import os
+import unittest
+from unittest import mock
+
+import errors
+import signing
+
+# Test 1 from RFC 8032 section 7.1
+SEED = bytes.fromhex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+PUBLIC_KEY = bytes.fromhex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+SIGNATURE = bytes.fromhex(
+    "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+)
+
+
+class SigningTest(unittest.TestCase):
+    def test_public_key(self):
+        self.assertEqual(signing.get_public_key(SEED), PUBLIC_KEY)
+
+    def test_sign_bytes(self):
+        self.assertEqual(signing.sign_bytes(SEED, b""), SIGNATURE)
+
+    def test_sign_without_key(self):
+        with mock.patch.dict(os.environ, {"DAY_SIGNING_KEY": ""}):
+            self.assertIsNone(signing.sign("{}"))
+
+    def test_sign_with_malformed_key(self):
+        for key in ("not base64!", base64.b64encode(SEED[:16]).decode()):
+            with mock.patch.dict(os.environ, {"DAY_SIGNING_KEY": key}):
+                with self.assertRaises(errors.ConfigError):
+                    signing.sign("")
+
+    def test_sign(self):
+        with mock.patch.dict(os.environ, {"DAY_SIGNING_KEY": base64.b64encode(SEED).decode()}):
+            signature = signing.sign("")
+        self.assertEqual(base64.b64decode(signature.public_key), PUBLIC_KEY)
+        self.assertEqual(base64.b64decode(signature.signature), SIGNATURE)
+
+    def test_signed_files(self):
+        with mock.patch.dict(os.environ, {"DAY_SIGNING_KEY": base64.b64encode(SEED).decode()}):
+            files = signing.get_signed_files("days/2024-06-01.json", "")
+        self.assertEqual(list(files), ["days/2024-06-01.json", "days/2024-06-01.json.sig"])
+        with mock.patch.dict(os.environ, {"DAY_SIGNING_KEY": ""}):
+            self.assertEqual(signing.get_signed_files("today.json", "{}"), {"today.json": "{}"})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,25 @@
+import unittest
+from datetime import datetime, timedelta, timezone
+
+from models import DateEntry, Days
+from sitemap import build_sitemap
+
+
+class SitemapTest(unittest.TestCase):
+    def test_leaves_out_days_before_publish_at(self):
+        tomorrow = datetime.now(timezone.utc) + timedelta(days=1)
+        days = Days(
+            days=[
+                DateEntry(date="2024-06-01", id=0),
+                DateEntry(date="2024-06-02", id=1, publish_at=datetime(2024, 6, 2, tzinfo=timezone.utc)),
+                DateEntry(date="2099-01-01", id=2, publish_at=tomorrow),
+            ]
+        )
+        sitemap = build_sitemap(days)
+        self.assertIn("/days/2024-06-01<", sitemap)
+        self.assertIn("/days/2024-06-02<", sitemap)
+        self.assertNotIn("2099-01-01", sitemap)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,65 @@
+import unittest
+from unittest import mock
+
+from staging import Staging
+
+
+# An in memory bucket standing in for the cdn module. Like S3, deleting a key that isn't there is fine.
+class FakeBucket:
+    def __init__(self, files: dict[str, bytes]):
+        self.files = dict(files)
+
+    def upload_bytes(self, data, key, content_type, public=True):
+        self.files[key] = data
+        return key
+
+    def exists(self, key):
+        return key in self.files
+
+    def copy(self, source_key, key, public=True):
+        self.files[key] = self.files[source_key]
+        return key
+
+    def delete(self, keys):
+        for key in keys:
+            self.files.pop(key, None)
+
+
+class StagingTest(unittest.TestCase):
+    def setUp(self):
+        self.bucket = FakeBucket({"days/2024-06-01.json": b"old day", "days.json": b"old index"})
+        patcher = mock.patch.multiple(
+            "staging.cdn",
+            upload_bytes=self.bucket.upload_bytes,
+            exists=self.bucket.exists,
+            copy=self.bucket.copy,
+            delete=self.bucket.delete,
+            get_url=lambda key: key,
+        )
+        patcher.start()
+        self.addCleanup(patcher.stop)
+        self.staging = Staging("run-1")
+        self.staging.upload_bytes(b"image", "2024-06-01/a.jpg", "image/jpeg")
+        self.staging.upload_bytes(b"new day", "days/2024-06-01.json", "application/json")
+
+    def test_finish(self):
+        self.staging.promote()
+        self.staging.protect(["days.json"])
+        self.bucket.files["days.json"] = b"new index"
+        self.staging.finish()
+        self.assertEqual(
+            self.bucket.files,
+            {"2024-06-01/a.jpg": b"image", "days/2024-06-01.json": b"new day", "days.json": b"new index"},
+        )
+
+    def test_roll_back(self):
+        self.staging.promote()
+        self.staging.protect(["days.json", "sitemap.xml"])
+        self.bucket.files["days.json"] = b"new index"
+        self.staging.roll_back()
+        self.staging.clean_up()
+        self.assertEqual(self.bucket.files, {"days/2024-06-01.json": b"old day", "days.json": b"old index"})
+
+
+if __name__ == "__main__":
+    unittest.main()
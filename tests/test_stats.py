@@ -0,0 +1,51 @@
+import unittest
+from datetime import datetime, timedelta, timezone
+from unittest import mock
+
+from models import DateEntry, Days
+from stats import build_stats
+from tests.test_published_json import make_day
+
+
+def make_days(*dates: str) -> Days:
+    return Days(days=[DateEntry(date=date, id=index) for index, date in enumerate(dates)])
+
+
+def make_day_for(date: str):
+    return make_day().model_copy(update={"date": date})
+
+
+@mock.patch("stats.fetch_day", side_effect=make_day_for)
+class StatsTest(unittest.TestCase):
+    def test_adds_to_previous(self, fetch_day):
+        previous = build_stats(make_days("2024-05-30", "2024-05-31"), make_day_for("2024-05-31"))
+        fetch_day.reset_mock()
+        stats = build_stats(make_days("2024-05-30", "2024-05-31", "2024-06-01"), make_day_for("2024-06-01"), previous)
+        fetch_day.assert_not_called()
+        self.assertEqual(stats.dates, ["2024-05-30", "2024-05-31", "2024-06-01"])
+        self.assertEqual(stats.days_published, 3)
+        self.assertEqual(stats.challenges_published, 12)
+        self.assertEqual(stats.word_usage["object"], {"apples": 12})
+        self.assertEqual(stats.providers, {"api.openai.com": 3})
+        self.assertEqual(previous.days_published, 2)
+
+    def test_counts_again_when_day_regenerated(self, fetch_day):
+        days = make_days("2024-05-31", "2024-06-01")
+        previous = build_stats(days, make_day_for("2024-06-01"))
+        fetch_day.reset_mock()
+        stats = build_stats(days, make_day_for("2024-06-01"), previous)
+        fetch_day.assert_called_once_with("2024-05-31")
+        self.assertEqual(stats.challenges_published, 8)
+
+    def test_leaves_out_days_before_publish_at(self, fetch_day):
+        days = make_days("2024-05-31")
+        days.days.append(
+            DateEntry(date="2099-01-01", id=1, publish_at=datetime.now(timezone.utc) + timedelta(days=1))
+        )
+        stats = build_stats(days, make_day_for("2099-01-01"))
+        self.assertEqual(stats.dates, ["2024-05-31"])
+        self.assertEqual(stats.challenges_published, 4)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,34 @@
+import json
+import os
+import tempfile
+import unittest
+from unittest import mock
+
+import words
+
+
+class NormalizeWordTest(unittest.TestCase):
+    def test_composes_and_trims(self):
+        self.assertEqual(words.normalize_word("  cafe\u0301 "), "café")
+
+    def test_straightens_quotes(self):
+        self.assertEqual(words.normalize_word("jack’s"), "jack's")
+
+    def test_collapses_whitespace(self):
+        self.assertEqual(words.normalize_word("hot  air\tballoon"), "hot air balloon")
+
+    def test_rejects_unusable(self):
+        self.assertIsNone(words.normalize_word("   "))
+        self.assertIsNone(words.normalize_word("bell\u0007"))
+        self.assertIsNone(words.normalize_word("zero\u200bwidth"))
+
+    def test_import_skips_unusable(self):
+        with tempfile.TemporaryDirectory() as directory:
+            with open(os.path.join(directory, "objects.json"), "w") as file:
+                json.dump([" apple", "", 3, "pear\u0000"], file)
+            with mock.patch.dict(os.environ, {"DATA_DIR": directory}), self.assertLogs("words", "WARNING"):
+                self.assertEqual(words.import_json_wordlist("objects.json"), ["apple"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,46 @@
+import math
+
+import config
+import keys
+from ai import EMBEDDING_MODEL, embed
+from models import Day, DayVectors, WordVector
+
+VECTOR_DIMENSIONS = 64
+QUANTIZE_SCALE = 127
+
+
+def get_vectors_key(date: str) -> str:
+    return keys.get_key("day_vectors", date=date)
+
+
+def cosine_similarity(a: list[float], b: list[float]) -> float:
+    dot = sum(x * y for x, y in zip(a, b))
+    norm = math.sqrt(sum(x * x for x in a)) * math.sqrt(sum(y * y for y in b))
+    return dot / norm if norm else 0.0
+
+
+def quantize(vector: list[float]) -> list[int]:
+    norm = math.sqrt(sum(value * value for value in vector)) or 1.0
+    return [round(value / norm * QUANTIZE_SCALE) for value in vector]
+
+
+def build_day_vectors(day: Day) -> DayVectors:
+    dimensions = int(config.get("VECTOR_DIMENSIONS", VECTOR_DIMENSIONS))
+    challenges = day.challenges.present()
+    words = [word.word for challenge in challenges.values() for word in challenge.words]
+    embeddings = iter(embed(words, dimensions))
+
+    return DayVectors(
+        date=day.date,
+        model=config.get("EMBEDDING_MODEL", EMBEDDING_MODEL),
+        dimensions=dimensions,
+        scale=1 / QUANTIZE_SCALE,
+        challenges={
+            difficulty: [
+                WordVector(word=word.word, vector=quantize(next(embeddings)))
+                for word in challenge.words
+            ]
+            for difficulty, challenge in challenges.items()
+        },
+    )
+
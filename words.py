@@ -1,13 +1,55 @@
 import json
 import logging
+import os
 import random
+import unicodedata
+
+import config
 
 from models import Difficulty, Word, WordsForDay
 
+logger = logging.getLogger(__name__)
+
+WORD_LISTS = {
+    "object": "objects.json",
+    "gerund": "gerunds.json",
+    "concept": "concepts.json",
+}
+# The word lists ship next to the code, so they're found wherever the generator is run from
+DATA_DIR = os.path.dirname(os.path.abspath(__file__))
+
+
+def get_data_dir() -> str:
+    return config.get("DATA_DIR", DATA_DIR)
+
+
+# Curly quotes and dashes pasted in from elsewhere, so the same word is always written the same way
+PUNCTUATION = str.maketrans({"\u2018": "'", "\u2019": "'", "\u201c": '"', "\u201d": '"', "\u2013": "-", "\u2014": "-"})
+
+
+# Returns None for entries that can't be used as a word: empty, or with control or other invisible characters
+def normalize_word(word: str) -> str | None:
+    word = " ".join(unicodedata.normalize("NFC", word).translate(PUNCTUATION).split())
+    if not word or any(unicodedata.category(character).startswith("C") for character in word):
+        return None
+    return word
+
 
 def import_json_wordlist(filename: str) -> list[str]:
-    with open(filename, "r") as file:
-        return json.loads(file.read())
+    with open(os.path.join(get_data_dir(), filename), "r") as file:
+        entries = json.loads(file.read())
+    words = []
+    for entry in entries:
+        word = normalize_word(entry) if isinstance(entry, str) else None
+        if word is None:
+            logger.warning("Skipping unusable entry %r in %s", entry, filename)
+        else:
+            words.append(word)
+    return words
+
+
+def load_words_of_type(word_type: str) -> list[str]:
+    return import_json_wordlist(WORD_LISTS[word_type])
 
 
 def generate_word_list(difficulty: Difficulty) -> list[Word]: